@@ -0,0 +1,249 @@
+//! This module fits the rendered prompt into a token budget by cutting down the file list.
+//!
+//! The tokens over the budget are spread across the files large enough to be truncated, in
+//! proportion to their size: each file is truncated by its share if enough of it can be kept,
+//! otherwise its content is elided. Smaller files are elided, largest first, once no file can be
+//! truncated, and when nothing is left to elide, files are dropped from the end of the list.
+
+use crate::token::Tokenizer;
+use anyhow::{anyhow, Result};
+use serde_json::Value;
+
+/// The minimum number of tokens a file must keep to be truncated instead of elided.
+const MIN_TRUNCATED_TOKENS: usize = 256;
+
+/// The placeholder that replaces the content of an elided file.
+const ELIDED_PLACEHOLDER: &str = "[content elided to fit the token budget]";
+
+/// The start of the line that marks where a file was truncated.
+const TRUNCATION_PREFIX: &str = "... [truncated: ";
+
+/// The action taken on a file to make the prompt fit the budget.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CutAction {
+    Truncated,
+    Elided,
+    Dropped,
+}
+
+/// A record of a file that was cut to make the prompt fit the budget.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Cut {
+    pub path: String,
+    pub action: CutAction,
+    pub tokens_saved: usize,
+}
+
+/// Renders the prompt and cuts files until the token count fits into `max_tokens`.
+///
+/// # Arguments
+///
+/// * `files` - The JSON file representations returned by `traverse_directory`; cut in place.
+/// * `max_tokens` - The token budget for the rendered prompt.
+/// * `tokenizer` - The tokenizer used to count tokens.
+/// * `render` - A closure rendering the prompt for the given files.
+///
+/// # Returns
+///
+/// * `Result<(String, usize, Vec<Cut>)>` - The rendered prompt, its token count and the cuts made.
+pub fn fit_to_budget<F>(
+    files: &mut Vec<Value>,
+    max_tokens: usize,
+    tokenizer: &dyn Tokenizer,
+    mut render: F,
+) -> Result<(String, usize, Vec<Cut>)>
+where
+    F: FnMut(&[Value]) -> Result<String>,
+{
+    let mut cuts = Vec::new();
+
+    loop {
+        let rendered = render(files)?;
+        let token_count = tokenizer.count(&rendered);
+        if token_count <= max_tokens {
+            return Ok((rendered, token_count, cuts));
+        }
+
+        if !cut_files(files, token_count - max_tokens, tokenizer, &mut cuts) {
+            return Err(anyhow!(
+                "無法將提示縮減到 {} tokens 以內 (移除所有文件後仍有 {} tokens)",
+                max_tokens,
+                token_count
+            ));
+        }
+    }
+}
+
+/// Cuts files to save `overrun` tokens, each file giving up a share of the overrun in
+/// proportion to its tokens.
+///
+/// Returns `false` when there was nothing left to cut.
+fn cut_files(
+    files: &mut Vec<Value>,
+    mut overrun: usize,
+    tokenizer: &dyn Tokenizer,
+    cuts: &mut Vec<Cut>,
+) -> bool {
+    let placeholder_tokens = tokenizer.count(ELIDED_PLACEHOLDER);
+    let mut candidates: Vec<(usize, usize)> = files
+        .iter()
+        .enumerate()
+        .filter(|(_, file)| !is_elided(file))
+        .map(|(index, file)| (index, code_tokens(file, tokenizer)))
+        .filter(|(_, tokens)| *tokens > placeholder_tokens)
+        .collect();
+    candidates.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+
+    // The overrun is spread across the files large enough to be truncated; only when there are
+    // none are the smaller files elided, largest first
+    let truncatable = candidates
+        .iter()
+        .take_while(|(_, tokens)| *tokens > MIN_TRUNCATED_TOKENS)
+        .count();
+    if truncatable > 0 {
+        candidates.truncate(truncatable);
+    }
+    let total_tokens: usize = candidates.iter().map(|(_, tokens)| tokens).sum();
+
+    let mut progress = false;
+    let mut saved_total = 0;
+    for (index, tokens) in candidates {
+        if truncatable == 0 && saved_total >= overrun {
+            break;
+        }
+
+        // Files falling below the minimum after giving up their share are elided instead
+        let share = (overrun * tokens).div_ceil(total_tokens);
+        let file = &mut files[index];
+        let (code, action) = if tokens >= share + MIN_TRUNCATED_TOKENS {
+            let code = truncate_code(
+                file["code"].as_str().unwrap_or(""),
+                tokens - share,
+                tokenizer,
+            );
+            (code, CutAction::Truncated)
+        } else {
+            (ELIDED_PLACEHOLDER.to_string(), CutAction::Elided)
+        };
+
+        let new_tokens = tokenizer.count(&code);
+        let saved = tokens.saturating_sub(new_tokens);
+        file["code"] = Value::String(code);
+        file["tokens"] = Value::from(new_tokens);
+        let flag = match action {
+            CutAction::Elided => "elided",
+            _ => "truncated",
+        };
+        file[flag] = Value::Bool(true);
+        record_cut(cuts, file_path(file), action, saved);
+        saved_total += saved;
+        progress = true;
+    }
+
+    if progress {
+        return true;
+    }
+
+    // Nothing is left to truncate or elide, so drop files starting from the end of the list.
+    while overrun > 0 {
+        let Some(file) = files.pop() else {
+            break;
+        };
+        let saved = rendered_tokens(&file, tokenizer);
+        record_cut(cuts, file_path(&file), CutAction::Dropped, saved);
+
+        overrun = overrun.saturating_sub(saved);
+        progress = true;
+    }
+
+    progress
+}
+
+/// Keeps the leading lines of a code block that fit into `keep_tokens` and marks the cut.
+///
+/// A closing markdown delimiter is preserved so that the code block stays well-formed, and a
+/// marker left by an earlier truncation is replaced rather than repeated.
+fn truncate_code(code: &str, keep_tokens: usize, tokenizer: &dyn Tokenizer) -> String {
+    let mut lines: Vec<&str> = code.lines().collect();
+    let closing = match lines.last() {
+        Some(last) if lines.len() > 1 && last.starts_with("```") => lines.pop(),
+        _ => None,
+    };
+    let mut omitted = match lines.last().and_then(|last| parse_truncation_marker(last)) {
+        Some(omitted) => {
+            lines.pop();
+            omitted
+        }
+        None => 0,
+    };
+
+    let marker_tokens = tokenizer.count(&truncation_marker(lines.len() + omitted));
+    let keep_tokens = keep_tokens.saturating_sub(marker_tokens + 1);
+
+    let mut truncated = String::new();
+    let mut used = 0;
+    let mut kept = 0;
+    for line in &lines {
+        used += tokenizer.count(line) + 1;
+        if used > keep_tokens {
+            break;
+        }
+        truncated.push_str(line);
+        truncated.push('\n');
+        kept += 1;
+    }
+    omitted += lines.len() - kept;
+
+    truncated.push_str(&truncation_marker(omitted));
+    truncated.push('\n');
+    if let Some(closing) = closing {
+        truncated.push_str(closing);
+    }
+    truncated
+}
+
+fn truncation_marker(omitted: usize) -> String {
+    format!(
+        "{}{} more lines omitted to fit the token budget]",
+        TRUNCATION_PREFIX, omitted
+    )
+}
+
+fn parse_truncation_marker(line: &str) -> Option<usize> {
+    line.strip_prefix(TRUNCATION_PREFIX)?
+        .split_whitespace()
+        .next()?
+        .parse()
+        .ok()
+}
+
+fn record_cut(cuts: &mut Vec<Cut>, path: String, action: CutAction, tokens_saved: usize) {
+    if let Some(cut) = cuts.iter_mut().find(|cut| cut.path == path) {
+        cut.action = action;
+        cut.tokens_saved += tokens_saved;
+    } else {
+        cuts.push(Cut {
+            path,
+            action,
+            tokens_saved,
+        });
+    }
+}
+
+fn code_tokens(file: &Value, tokenizer: &dyn Tokenizer) -> usize {
+    tokenizer.count(file["code"].as_str().unwrap_or(""))
+}
+
+/// Returns the tokens of the code of a file and of the path header rendered above it.
+fn rendered_tokens(file: &Value, tokenizer: &dyn Tokenizer) -> usize {
+    let header = format!("`{}`:\n\n", file["path"].as_str().unwrap_or(""));
+    tokenizer.count(&header) + code_tokens(file, tokenizer)
+}
+
+fn is_elided(file: &Value) -> bool {
+    file["elided"].as_bool().unwrap_or(false)
+}
+
+fn file_path(file: &Value) -> String {
+    file["path"].as_str().unwrap_or("").to_string()
+}
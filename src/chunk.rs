@@ -0,0 +1,493 @@
+//! This module splits source files into chunks along syntactic boundaries.
+//!
+//! A boundary is a line that starts a top-level definition such as a function, impl block,
+//! struct or class, together with the comments, attributes and decorators directly above it.
+//! Definitions larger than the limit are split at nested definitions, and then between lines.
+//! Files can also be reduced to the regions around the matches of a regular expression, or to
+//! the definitions of named symbols.
+
+use crate::token::Tokenizer;
+use once_cell::sync::Lazy;
+use regex::Regex;
+use std::ops::Range;
+
+static RUST_DEFINITION: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r#"^(pub(\([^)]*\))?\s+)?((async|const|unsafe|default)\s+)*(extern\s+"[^"]*"\s+)?((fn|impl|struct|enum|trait|mod|type|union)\b|macro_rules!)"#).unwrap()
+});
+static PYTHON_DEFINITION: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"^(async\s+def|def|class)\b").unwrap());
+static JAVASCRIPT_DEFINITION: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r"^(export\s+)?(default\s+)?(declare\s+)?(abstract\s+)?(async\s+)?(function\*?|class|interface|type|enum|namespace|const|let|var)\b").unwrap()
+});
+static GO_DEFINITION: Lazy<Regex> = Lazy::new(|| Regex::new(r"^(func|type|var|const)\b").unwrap());
+static RUBY_DEFINITION: Lazy<Regex> = Lazy::new(|| Regex::new(r"^(def|class|module)\b").unwrap());
+
+static RUST_NAME: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r"\b(?:fn|struct|enum|trait|union|type|mod)\s+([A-Za-z_]\w*)|\bmacro_rules!\s*([A-Za-z_]\w*)").unwrap()
+});
+static RUST_IMPL_NAME: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r"^(?:(?:unsafe|default)\s+)*impl\b(?:\s*<[^>]*>)?\s+(?:[^{]*?\bfor\s+)?(?:[A-Za-z_]\w*::)*([A-Za-z_]\w*)").unwrap()
+});
+static PYTHON_NAME: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"\b(?:def|class)\s+([A-Za-z_]\w*)").unwrap());
+static JAVASCRIPT_NAME: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(
+        r"\b(?:function\*?|class|interface|type|enum|namespace|const|let|var)\s+([A-Za-z_$][\w$]*)",
+    )
+    .unwrap()
+});
+static GO_NAME: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"\b(?:func\s+(?:\([^)]*\)\s*)?|type\s+)([A-Za-z_]\w*)").unwrap());
+static RUBY_NAME: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"\b(?:def|class|module)\s+(?:self\.)?([A-Za-z_]\w*[?!]?)").unwrap());
+
+/// The prefixes of lines that belong to the definition below them.
+const ATTACHED_PREFIXES: &[&str] = &["#", "//", "/*", "*", "@"];
+
+/// A chunk of a file, covering a range of lines.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Chunk {
+    /// The 1-based number of the first line of the chunk.
+    pub start_line: usize,
+    /// The 1-based number of the last line of the chunk.
+    pub end_line: usize,
+    /// The code of the chunk.
+    pub code: String,
+}
+
+/// Splits the code into chunks of at most `max_tokens` tokens, breaking at syntactic boundaries.
+///
+/// # Arguments
+///
+/// * `code` - The code of the file.
+/// * `extension` - The file extension, used to recognize definitions.
+/// * `max_tokens` - The maximum number of tokens of each chunk.
+/// * `tokenizer` - The tokenizer used to count tokens.
+///
+/// # Returns
+///
+/// * `Vec<Chunk>` - The chunks in file order, covering every line of the file.
+pub fn chunk_code(
+    code: &str,
+    extension: &str,
+    max_tokens: usize,
+    tokenizer: &dyn Tokenizer,
+) -> Vec<Chunk> {
+    let lines: Vec<&str> = code.lines().collect();
+    let count = |range: &Range<usize>| tokenizer.count(&lines[range.clone()].join("\n"));
+
+    let mut units = Vec::new();
+    for unit in split_at_definitions(&lines, 0..lines.len(), extension, false) {
+        if count(&unit) <= max_tokens {
+            units.push(unit);
+            continue;
+        }
+        for nested in split_at_definitions(&lines, unit, extension, true) {
+            if count(&nested) <= max_tokens {
+                units.push(nested);
+            } else {
+                units.extend(split_between_lines(&lines, nested, max_tokens, tokenizer));
+            }
+        }
+    }
+
+    let mut ranges: Vec<Range<usize>> = Vec::new();
+    for unit in units {
+        match ranges.last_mut() {
+            Some(last) if count(&(last.start..unit.end)) <= max_tokens => last.end = unit.end,
+            _ => ranges.push(unit),
+        }
+    }
+
+    ranges
+        .into_iter()
+        .map(|range| Chunk {
+            start_line: range.start + 1,
+            end_line: range.end,
+            code: lines[range].join("\n"),
+        })
+        .collect()
+}
+
+/// Splits the chunks of more than `max_tokens` tokens at syntactic boundaries, like `chunk_code`.
+///
+/// # Arguments
+///
+/// * `chunks` - The chunks of a file, such as its matching regions or symbol definitions.
+/// * `extension` - The file extension, used to recognize definitions.
+/// * `max_tokens` - The maximum number of tokens of each chunk.
+/// * `tokenizer` - The tokenizer used to count tokens.
+///
+/// # Returns
+///
+/// * `Vec<Chunk>` - The chunks in file order, with line numbers relative to the file.
+pub fn split_large_chunks(
+    chunks: Vec<Chunk>,
+    extension: &str,
+    max_tokens: usize,
+    tokenizer: &dyn Tokenizer,
+) -> Vec<Chunk> {
+    chunks
+        .into_iter()
+        .flat_map(|chunk| {
+            if tokenizer.count(&chunk.code) <= max_tokens {
+                return vec![chunk];
+            }
+            let offset = chunk.start_line - 1;
+            chunk_code(&chunk.code, extension, max_tokens, tokenizer)
+                .into_iter()
+                .map(|part| Chunk {
+                    start_line: part.start_line + offset,
+                    end_line: part.end_line + offset,
+                    code: part.code,
+                })
+                .collect()
+        })
+        .collect()
+}
+
+/// Returns the regions of the code around the matches of a regular expression.
+///
+/// Each region spans the matching lines plus `context` lines before and after them, and
+/// overlapping or adjacent regions are merged.
+///
+/// # Arguments
+///
+/// * `code` - The code of the file.
+/// * `regex` - The regular expression to search for.
+/// * `context` - The number of context lines around each match.
+///
+/// # Returns
+///
+/// * `Vec<Chunk>` - The matching regions in file order, empty if nothing matches.
+pub fn matching_regions(code: &str, regex: &Regex, context: usize) -> Vec<Chunk> {
+    let lines: Vec<&str> = code.lines().collect();
+    let line_starts: Vec<usize> = std::iter::once(0)
+        .chain(code.match_indices('\n').map(|(i, _)| i + 1))
+        .collect();
+    let line_of = |offset: usize| line_starts.partition_point(|start| *start <= offset) - 1;
+
+    let last_line = lines.len().saturating_sub(1);
+
+    let mut ranges: Vec<Range<usize>> = Vec::new();
+    for found in regex.find_iter(code) {
+        let first = line_of(found.start()).min(last_line);
+        let last = line_of(found.end().max(found.start() + 1) - 1).min(last_line);
+        let range = first.saturating_sub(context)..(last + context + 1).min(lines.len());
+        match ranges.last_mut() {
+            Some(previous) if range.start <= previous.end => {
+                previous.end = previous.end.max(range.end)
+            }
+            _ => ranges.push(range),
+        }
+    }
+
+    ranges
+        .into_iter()
+        .map(|range| Chunk {
+            start_line: range.start + 1,
+            end_line: range.end,
+            code: lines[range].join("\n"),
+        })
+        .collect()
+}
+
+/// Returns the definitions of the named functions, types, classes and impl blocks in the code.
+///
+/// Each span covers a definition together with the comments, attributes and decorators directly
+/// above it, and overlapping spans are merged. Definitions are recognized in Rust, Python,
+/// JavaScript, TypeScript, Go and Ruby files.
+///
+/// # Arguments
+///
+/// * `code` - The code of the file.
+/// * `extension` - The file extension, used to recognize definitions.
+/// * `names` - The names of the symbols to extract.
+///
+/// # Returns
+///
+/// * `Vec<Chunk>` - The definitions in file order, empty if none of the symbols is defined.
+pub fn symbol_definitions(code: &str, extension: &str, names: &[String]) -> Vec<Chunk> {
+    let lines: Vec<&str> = code.lines().collect();
+
+    let mut spans: Vec<Range<usize>> = (0..lines.len())
+        .filter(|index| {
+            definition_name(lines[*index].trim_start(), extension)
+                .is_some_and(|name| names.iter().any(|symbol| symbol == name))
+                && is_definition(&lines, *index, extension, true)
+        })
+        .map(|index| {
+            let mut start = index;
+            while start > 0 && is_attached(lines[start - 1]) {
+                start -= 1;
+            }
+            start..definition_end(&lines, index, extension)
+        })
+        .collect();
+    spans.sort_by_key(|span| span.start);
+
+    let mut ranges: Vec<Range<usize>> = Vec::new();
+    for span in spans {
+        match ranges.last_mut() {
+            Some(previous) if span.start < previous.end => {
+                previous.end = previous.end.max(span.end)
+            }
+            _ => ranges.push(span),
+        }
+    }
+
+    ranges
+        .into_iter()
+        .map(|range| Chunk {
+            start_line: range.start + 1,
+            end_line: range.end,
+            code: lines[range].join("\n"),
+        })
+        .collect()
+}
+
+/// Splits the range of lines at the lines starting a definition.
+///
+/// Top-level definitions must start in the first column, while nested ones may be indented.
+fn split_at_definitions(
+    lines: &[&str],
+    range: Range<usize>,
+    extension: &str,
+    nested: bool,
+) -> Vec<Range<usize>> {
+    let mut starts = vec![range.start];
+    for index in range.start + 1..range.end {
+        if !is_definition(lines, index, extension, nested) {
+            continue;
+        }
+        let mut start = index;
+        while start > *starts.last().unwrap() + 1 && is_attached(lines[start - 1]) {
+            start -= 1;
+        }
+        if start > *starts.last().unwrap() {
+            starts.push(start);
+        }
+    }
+    starts.push(range.end);
+
+    starts.windows(2).map(|w| w[0]..w[1]).collect()
+}
+
+/// Splits the range of lines greedily so that each piece fits into `max_tokens` tokens.
+fn split_between_lines(
+    lines: &[&str],
+    range: Range<usize>,
+    max_tokens: usize,
+    tokenizer: &dyn Tokenizer,
+) -> Vec<Range<usize>> {
+    let mut pieces = Vec::new();
+    let mut start = range.start;
+    let mut tokens = 0;
+    for index in range.clone() {
+        let line_tokens = tokenizer.count(lines[index]) + 1;
+        if tokens + line_tokens > max_tokens && index > start {
+            pieces.push(start..index);
+            start = index;
+            tokens = 0;
+        }
+        tokens += line_tokens;
+    }
+    pieces.push(start..range.end);
+    pieces
+}
+
+fn is_definition(lines: &[&str], index: usize, extension: &str, nested: bool) -> bool {
+    let line = lines[index];
+    let indented = line.starts_with(char::is_whitespace);
+    if indented && !nested {
+        return false;
+    }
+
+    let trimmed = line.trim_start();
+    match extension {
+        "rs" => RUST_DEFINITION.is_match(trimmed),
+        "py" | "pyi" => PYTHON_DEFINITION.is_match(trimmed),
+        "js" | "jsx" | "mjs" | "cjs" | "ts" | "tsx" => JAVASCRIPT_DEFINITION.is_match(trimmed),
+        "go" => GO_DEFINITION.is_match(trimmed),
+        "rb" => RUBY_DEFINITION.is_match(trimmed),
+        // Other languages: a top-level line following a blank line starts a new definition.
+        _ => {
+            !indented
+                && trimmed.starts_with(|c: char| c.is_alphanumeric() || c == '_')
+                && lines[index - 1].trim().is_empty()
+        }
+    }
+}
+
+/// Returns the name defined by a trimmed line starting a definition.
+fn definition_name<'a>(line: &'a str, extension: &str) -> Option<&'a str> {
+    let name_regex: &Regex = match extension {
+        "rs" => {
+            if let Some(captures) = RUST_IMPL_NAME.captures(line) {
+                return captures.get(1).map(|name| name.as_str());
+            }
+            &RUST_NAME
+        }
+        "py" | "pyi" => &PYTHON_NAME,
+        "js" | "jsx" | "mjs" | "cjs" | "ts" | "tsx" => &JAVASCRIPT_NAME,
+        "go" => &GO_NAME,
+        "rb" => &RUBY_NAME,
+        _ => return None,
+    };
+    name_regex
+        .captures(line)
+        .and_then(|captures| captures.iter().skip(1).flatten().next())
+        .map(|name| name.as_str())
+}
+
+/// Returns the end, exclusive, of the definition starting at a line.
+///
+/// Python blocks end with their indentation and Ruby blocks with their `end` line. Elsewhere a
+/// definition ends with its closing brace, or with a `;` or a blank line when it has no body;
+/// braces inside comments and string and character literals are not counted.
+fn definition_end(lines: &[&str], index: usize, extension: &str) -> usize {
+    let indentation = |line: &str| line.len() - line.trim_start().len();
+    let indent = indentation(lines[index]);
+    match extension {
+        "py" | "pyi" => {
+            let header_end = (index..lines.len())
+                .find(|i| {
+                    lines[*i]
+                        .split('#')
+                        .next()
+                        .unwrap_or("")
+                        .trim_end()
+                        .ends_with(':')
+                })
+                .unwrap_or(index);
+            let mut end = header_end + 1;
+            for (i, line) in lines.iter().enumerate().skip(header_end + 1) {
+                if line.trim().is_empty() {
+                    continue;
+                }
+                if indentation(line) <= indent {
+                    break;
+                }
+                end = i + 1;
+            }
+            end
+        }
+        "rb" => (index + 1..lines.len())
+            .find(|i| indentation(lines[*i]) == indent && lines[*i].trim_start().starts_with("end"))
+            .map_or(lines.len(), |i| i + 1),
+        _ => {
+            let mut depth = 0usize;
+            let mut opened = false;
+            let mut context = Lexical::Code;
+            for (i, line) in lines.iter().enumerate().skip(index) {
+                if !opened && i > index && line.trim().is_empty() {
+                    return i;
+                }
+                for c in code_braces(line, extension, &mut context) {
+                    match c {
+                        '{' => {
+                            depth += 1;
+                            opened = true;
+                        }
+                        '}' => depth = depth.saturating_sub(1),
+                        _ => {}
+                    }
+                }
+                if (opened && depth == 0) || (!opened && line.trim_end().ends_with(';')) {
+                    return i + 1;
+                }
+            }
+            lines.len()
+        }
+    }
+}
+
+/// Where a line starts, given the lines before it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Lexical {
+    Code,
+    BlockComment,
+    /// A string delimited by the given quote, with backslash escapes unless it is a backtick.
+    String(char),
+    /// A Rust raw string closed by a quote followed by the given number of `#`.
+    RawString(usize),
+}
+
+/// Returns the braces of a line outside of comments and string and character literals.
+///
+/// The context is updated for strings and block comments continuing on the next line. In Rust
+/// and Go, single quotes only delimit character literals, so that Rust lifetimes are skipped.
+fn code_braces(line: &str, extension: &str, context: &mut Lexical) -> Vec<char> {
+    let chars: Vec<char> = line.chars().collect();
+    let char_literals = matches!(extension, "rs" | "go");
+    let mut braces = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        let next = chars.get(i + 1).copied();
+        match *context {
+            Lexical::BlockComment => {
+                if c == '*' && next == Some('/') {
+                    *context = Lexical::Code;
+                    i += 1;
+                }
+            }
+            Lexical::String(quote) => {
+                if c == '\\' && quote != '`' {
+                    i += 1;
+                } else if c == quote {
+                    *context = Lexical::Code;
+                }
+            }
+            Lexical::RawString(hashes) => {
+                if c == '"' && chars[i + 1..].iter().take_while(|c| **c == '#').count() >= hashes {
+                    *context = Lexical::Code;
+                    i += hashes;
+                }
+            }
+            Lexical::Code => match c {
+                '/' if next == Some('/') => break,
+                '/' if next == Some('*') => {
+                    *context = Lexical::BlockComment;
+                    i += 1;
+                }
+                'r' if extension == "rs"
+                    && matches!(next, Some('"' | '#'))
+                    && !chars[..i]
+                        .last()
+                        .is_some_and(|previous| previous.is_alphanumeric() || *previous == '_') =>
+                {
+                    let hashes = chars[i + 1..].iter().take_while(|c| **c == '#').count();
+                    if chars.get(i + 1 + hashes) == Some(&'"') {
+                        *context = Lexical::RawString(hashes);
+                        i += 1 + hashes;
+                    }
+                }
+                '\'' if char_literals => {
+                    // A character literal is either escaped or a single character, and the
+                    // escaped character may itself be a quote, as in '\''
+                    if next == Some('\\') {
+                        i += 3;
+                        while i < chars.len() && chars[i] != '\'' {
+                            i += 1;
+                        }
+                    } else if chars.get(i + 2) == Some(&'\'') {
+                        i += 2;
+                    }
+                }
+                '"' | '\'' | '`' => *context = Lexical::String(c),
+                '{' | '}' => braces.push(c),
+                _ => {}
+            },
+        }
+        i += 1;
+    }
+    braces
+}
+
+fn is_attached(line: &str) -> bool {
+    let trimmed = line.trim_start();
+    ATTACHED_PREFIXES
+        .iter()
+        .any(|prefix| trimmed.starts_with(prefix))
+}
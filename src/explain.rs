@@ -0,0 +1,306 @@
+//! This module explains why each file of a traversal is included in or excluded from the prompt.
+
+use crate::filter::{is_generated, FilterReason};
+use crate::path::{
+    build_default_excludes, build_filter_set, build_walker, is_in_excluded_directory,
+    TraverseOptions, IGNORE_FILE_NAME,
+};
+use anyhow::Result;
+use ignore::gitignore::{Gitignore, GitignoreBuilder};
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+/// The ignore files of a directory, from the highest to the lowest precedence.
+const IGNORE_FILES: &[&str] = &[IGNORE_FILE_NAME, ".ignore", ".gitignore"];
+
+/// The reason deciding whether a path is included.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ExplainReason {
+    /// The path is hidden and hidden files are not included.
+    Hidden,
+    /// The path is ignored by a rule of an ignore file, if the rule could be found.
+    Ignored {
+        source: Option<PathBuf>,
+        pattern: Option<String>,
+    },
+    /// The path matches a pattern of the default exclusion profile.
+    DefaultExclude { pattern: String },
+    /// The include and exclude filters decide.
+    Filter(FilterReason),
+    /// The file header marks it as generated code.
+    Generated,
+    /// The file is empty.
+    Empty,
+    /// The file is not valid UTF-8 text.
+    NotUtf8,
+    /// The file could not be read.
+    Unreadable(String),
+}
+
+impl fmt::Display for ExplainReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ExplainReason::Hidden => write!(f, "隱藏文件，使用 --hidden 包含"),
+            ExplainReason::Ignored {
+                source: Some(source),
+                pattern: Some(pattern),
+            } => write!(f, "被 {} 中的規則 '{}' 忽略", source.display(), pattern),
+            ExplainReason::Ignored { .. } => write!(f, "被忽略文件排除"),
+            ExplainReason::DefaultExclude { pattern } => write!(
+                f,
+                "匹配默認排除模式 '{}'，使用 --no-default-excludes 包含",
+                pattern
+            ),
+            ExplainReason::Filter(reason) => write!(f, "{}", reason),
+            ExplainReason::Generated => {
+                write!(f, "生成的代碼，使用 --no-default-excludes 包含")
+            }
+            ExplainReason::Empty => write!(f, "文件為空"),
+            ExplainReason::NotUtf8 => write!(f, "不是有效的 UTF-8 文本"),
+            ExplainReason::Unreadable(error) => write!(f, "無法讀取: {}", error),
+        }
+    }
+}
+
+/// Whether a path is included, and why.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Explanation {
+    /// The path relative to the traversal root.
+    pub path: PathBuf,
+    /// Whether the path is a directory skipped as a whole.
+    pub is_dir: bool,
+    /// Whether the path is included in the prompt.
+    pub included: bool,
+    /// The deciding reason.
+    pub reason: ExplainReason,
+}
+
+/// Walks the directory like `traverse_directory` and explains the decision taken for every file.
+///
+/// Hidden, ignored and default-excluded directories are reported once instead of file by file.
+/// Only the ignore, hidden, symlink, depth and default exclusion options and the include and
+/// exclude filters are considered.
+///
+/// # Arguments
+///
+/// * `root_path` - The path to the root directory.
+/// * `options` - The traversal options.
+///
+/// # Returns
+///
+/// * `Result<Vec<Explanation>>` - The explanations sorted by path, or an error if the filters are invalid.
+pub fn explain_directory(root_path: &Path, options: &TraverseOptions) -> Result<Vec<Explanation>> {
+    let canonical_root_path = root_path.canonicalize()?;
+    let filter_set = build_filter_set(&canonical_root_path, options)?;
+    let default_excludes = build_default_excludes(&canonical_root_path, options);
+
+    let visited: Arc<HashSet<PathBuf>> = Arc::new(
+        build_walker(
+            &canonical_root_path,
+            options,
+            &filter_set,
+            default_excludes.as_ref(),
+        )
+        .build()
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.into_path())
+        .collect(),
+    );
+
+    // Walk again without ignore files and hidden checks, stopping below the skipped paths
+    let unfiltered = TraverseOptions {
+        hidden: true,
+        no_ignore: true,
+        ..options.clone()
+    };
+    let mut walker = build_walker(
+        &canonical_root_path,
+        &unfiltered,
+        &filter_set,
+        default_excludes.as_ref(),
+    );
+    let filter_visited = Arc::clone(&visited);
+    let filter_root = canonical_root_path.clone();
+    let filter_filter_set = filter_set.clone();
+    let filter_default_excludes = default_excludes.clone();
+    walker.filter_entry(move |entry| {
+        entry.file_name() != ".git"
+            && !is_in_excluded_directory(
+                &filter_root,
+                &filter_filter_set,
+                filter_default_excludes.as_ref(),
+                entry,
+            )
+            && (filter_visited.contains(entry.path())
+                || entry
+                    .path()
+                    .parent()
+                    .is_some_and(|parent| filter_visited.contains(parent)))
+    });
+
+    let mut ignore_files = IgnoreFiles::new(&canonical_root_path);
+    let mut explanations = Vec::new();
+    for entry in walker.build().filter_map(|entry| entry.ok()) {
+        let path = entry.path();
+        let Ok(relative_path) = path.strip_prefix(&canonical_root_path) else {
+            continue;
+        };
+        if relative_path.as_os_str().is_empty() {
+            continue;
+        }
+        let is_dir = entry
+            .file_type()
+            .is_some_and(|file_type| file_type.is_dir());
+
+        if !visited.contains(path) {
+            let hidden = entry.file_name().to_string_lossy().starts_with('.');
+            let reason = if hidden && !options.hidden {
+                ExplainReason::Hidden
+            } else {
+                ignore_files.explain(path, is_dir)
+            };
+            explanations.push(Explanation {
+                path: relative_path.to_path_buf(),
+                is_dir,
+                included: false,
+                reason,
+            });
+            continue;
+        }
+        let default_pattern = default_excludes
+            .as_ref()
+            .and_then(|default_excludes| default_excludes.matching_pattern(relative_path, is_dir))
+            .filter(|_| !filter_set.is_explicitly_included(relative_path, is_dir))
+            // Directories the walker entered for explicitly included paths are not reported
+            .filter(|_| {
+                !is_dir
+                    || !visited
+                        .iter()
+                        .any(|visited| visited != path && visited.starts_with(path))
+            });
+        if let Some(pattern) = default_pattern {
+            explanations.push(Explanation {
+                path: relative_path.to_path_buf(),
+                is_dir,
+                included: false,
+                reason: ExplainReason::DefaultExclude {
+                    pattern: pattern.to_string(),
+                },
+            });
+            continue;
+        }
+        if is_dir {
+            continue;
+        }
+
+        let (included, filter_reason) = filter_set.explain(relative_path, is_dir);
+        let (included, reason) = if !included {
+            (false, ExplainReason::Filter(filter_reason))
+        } else {
+            match fs::read(path) {
+                Err(e) => (false, ExplainReason::Unreadable(e.to_string())),
+                Ok(bytes) => {
+                    let code = String::from_utf8_lossy(&bytes);
+                    if code.contains(char::REPLACEMENT_CHARACTER) {
+                        (false, ExplainReason::NotUtf8)
+                    } else if code.trim().is_empty() {
+                        (false, ExplainReason::Empty)
+                    } else if default_excludes.is_some() && is_generated(&code) {
+                        (false, ExplainReason::Generated)
+                    } else {
+                        (true, ExplainReason::Filter(filter_reason))
+                    }
+                }
+            }
+        };
+        explanations.push(Explanation {
+            path: relative_path.to_path_buf(),
+            is_dir,
+            included,
+            reason,
+        });
+    }
+
+    explanations.sort_by(|a, b| a.path.cmp(&b.path));
+    Ok(explanations)
+}
+
+/// The ignore files read so far, cached by directory.
+struct IgnoreFiles {
+    /// The directory above which ignore files are not read, the repository or the root.
+    top: PathBuf,
+    /// The repository exclude file and the global gitignore, read once.
+    shared: Vec<Gitignore>,
+    directories: HashMap<PathBuf, Vec<Gitignore>>,
+}
+
+impl IgnoreFiles {
+    /// Finds the repository containing the traversal root and reads its exclude file and the
+    /// global gitignore.
+    fn new(root: &Path) -> Self {
+        let repository = root
+            .ancestors()
+            .find(|directory| directory.join(".git").exists());
+
+        let mut shared = Vec::new();
+        if let Some(repository) = repository {
+            let mut builder = GitignoreBuilder::new(repository);
+            builder.add(repository.join(".git/info/exclude"));
+            shared.extend(builder.build().ok());
+        }
+        shared.push(Gitignore::global().0);
+
+        IgnoreFiles {
+            top: repository.unwrap_or(root).to_path_buf(),
+            shared,
+            directories: HashMap::new(),
+        }
+    }
+
+    /// Finds the ignore rule matching a path skipped by the walker.
+    ///
+    /// Deeper ignore files take precedence over the ones above them, followed by the repository
+    /// exclude file and the global gitignore.
+    fn explain(&mut self, path: &Path, is_dir: bool) -> ExplainReason {
+        let mut matchers = Vec::new();
+        for directory in path.ancestors().skip(1) {
+            matchers.extend(self.directory(directory).iter().cloned());
+            if directory == self.top {
+                break;
+            }
+        }
+
+        for matcher in matchers.iter().chain(&self.shared) {
+            let matched = matcher.matched(path, is_dir);
+            if matched.is_whitelist() {
+                break;
+            }
+            if let Some(glob) = matched.inner().filter(|_| matched.is_ignore()) {
+                return ExplainReason::Ignored {
+                    source: glob.from().map(Path::to_path_buf),
+                    pattern: Some(glob.original().to_string()),
+                };
+            }
+        }
+        ExplainReason::Ignored {
+            source: None,
+            pattern: None,
+        }
+    }
+
+    fn directory(&mut self, directory: &Path) -> &[Gitignore] {
+        self.directories
+            .entry(directory.to_path_buf())
+            .or_insert_with(|| {
+                IGNORE_FILES
+                    .iter()
+                    .map(|name| directory.join(name))
+                    .filter(|file| file.is_file())
+                    .map(|file| Gitignore::new(file).0)
+                    .collect()
+            })
+    }
+}
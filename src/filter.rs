@@ -1,10 +1,642 @@
+use anyhow::{anyhow, Context, Result};
+use ignore::gitignore::{Gitignore, GitignoreBuilder};
 use log::{debug, error, warn};
 use regex::Regex;
+use std::fmt;
 use std::fs;
-use std::path::Path;
+use std::path::{Path, PathBuf};
+
+/// Compiles gitignore-style glob patterns anchored at the traversal root into one matcher.
+///
+/// The patterns follow `.gitignore` rules: a pattern without a slash matches a file or directory
+/// name at any depth, a leading slash anchors it at the root, a trailing slash matches only
+/// directories, and `*`, `**`, `?`, `[abc]` and `{a,b}` are supported.
+///
+/// # Arguments
+///
+/// * `root` - The path to the root directory of the traversal.
+/// * `patterns` - The glob patterns.
+///
+/// # Returns
+///
+/// * `Result<Gitignore>` - The compiled patterns, or an error if a pattern is not a valid glob.
+pub fn build_glob_matcher(root: &Path, patterns: &[String]) -> Result<Gitignore> {
+    let mut builder = GitignoreBuilder::new(root);
+    for pattern in patterns {
+        builder
+            .add_line(None, pattern)
+            .with_context(|| format!("無效的 glob 模式 '{}'", pattern))?;
+    }
+    builder.build().context("無法編譯 glob 模式")
+}
+
+/// The gitignore-style patterns of the built-in exclusion profile, applied unless disabled.
+pub const DEFAULT_EXCLUDES: &[&str] = &[
+    "Cargo.lock",
+    "package-lock.json",
+    "yarn.lock",
+    "pnpm-lock.yaml",
+    "poetry.lock",
+    "Gemfile.lock",
+    "composer.lock",
+    "*.min.js",
+    "*.min.css",
+    "*.js.map",
+    "*.css.map",
+    "*.d.ts.map",
+    "node_modules/",
+    "vendor/",
+    "dist/",
+];
+
+/// The markers in the header of a file that identify it as generated code.
+const GENERATED_MARKERS: &[&str] = &["@generated", "DO NOT EDIT"];
+
+/// The number of leading lines searched for a generated-code marker.
+const GENERATED_HEADER_LINES: usize = 5;
+
+/// The compiled built-in exclusion profile for lockfiles, dependencies, build output, minified
+/// assets and source maps.
+#[derive(Debug, Clone)]
+pub struct DefaultExcludes {
+    matcher: Gitignore,
+}
+
+impl DefaultExcludes {
+    /// Compiles the `DEFAULT_EXCLUDES` patterns anchored at the traversal root.
+    pub fn new(root: &Path) -> Self {
+        let patterns: Vec<String> = DEFAULT_EXCLUDES.iter().map(|p| p.to_string()).collect();
+        DefaultExcludes {
+            matcher: build_glob_matcher(root, &patterns).expect("默認排除模式無效"),
+        }
+    }
+
+    /// Returns the default pattern excluding a path relative to the traversal root, if any.
+    ///
+    /// # Arguments
+    ///
+    /// * `relative_path` - The path relative to the traversal root.
+    /// * `is_dir` - Whether the path is a directory.
+    ///
+    /// # Returns
+    ///
+    /// * `Option<&str>` - The matching pattern, or `None` if the path is not excluded.
+    pub fn matching_pattern(&self, relative_path: &Path, is_dir: bool) -> Option<&str> {
+        if relative_path.as_os_str().is_empty() {
+            return None;
+        }
+        let matched = self
+            .matcher
+            .matched_path_or_any_parents(relative_path, is_dir);
+        matched
+            .inner()
+            .filter(|_| matched.is_ignore())
+            .map(|glob| glob.original())
+    }
+}
+
+/// Determines whether code is generated, based on an `@generated` or `DO NOT EDIT` marker in
+/// its first lines.
+///
+/// # Arguments
+///
+/// * `code` - The content of the file.
+///
+/// # Returns
+///
+/// * `bool` - `true` if the header marks the code as generated, `false` otherwise.
+pub fn is_generated(code: &str) -> bool {
+    code.lines()
+        .take(GENERATED_HEADER_LINES)
+        .any(|line| GENERATED_MARKERS.iter().any(|marker| line.contains(marker)))
+}
+
+/// Whether a rule includes or excludes the paths it matches.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RuleAction {
+    Include,
+    Exclude,
+}
+
+/// The reason a filter set includes or excludes a path.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FilterReason {
+    /// The path matches a single pattern, or only patterns of the same kind.
+    Pattern { action: RuleAction, pattern: String },
+    /// The path matches both an include and an exclude pattern, and the winner decides.
+    Conflict {
+        include: String,
+        exclude: String,
+        winner: RuleAction,
+    },
+    /// The path matches none of the include patterns.
+    NoIncludeMatch,
+    /// The path matches no pattern and is included by default.
+    Unfiltered,
+}
+
+impl fmt::Display for FilterReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FilterReason::Pattern {
+                action: RuleAction::Include,
+                pattern,
+            } => write!(f, "匹配包含模式 '{}'", pattern),
+            FilterReason::Pattern {
+                action: RuleAction::Exclude,
+                pattern,
+            } => write!(f, "匹配排除模式 '{}'", pattern),
+            FilterReason::Conflict {
+                include,
+                exclude,
+                winner,
+            } => write!(
+                f,
+                "同時匹配包含模式 '{}' 和排除模式 '{}'，{}優先",
+                include,
+                exclude,
+                match winner {
+                    RuleAction::Include => "包含",
+                    RuleAction::Exclude => "排除",
+                }
+            ),
+            FilterReason::NoIncludeMatch => write!(f, "未匹配任何包含模式"),
+            FilterReason::Unfiltered => write!(f, "未匹配任何模式"),
+        }
+    }
+}
+
+/// An ordered list of include and exclude rules, in which the last matching rule wins.
+///
+/// Paths matched by no rule are excluded when the first rule is an include rule, and included
+/// otherwise, so `+src/**` selects only `src` while `-target/` keeps everything else.
+#[derive(Debug, Clone)]
+pub struct RuleSet {
+    root: PathBuf,
+    rules: Vec<(RuleAction, Gitignore)>,
+    unmatched_included: Option<bool>,
+}
+
+impl RuleSet {
+    /// Returns an empty rule set for the traversal root.
+    pub fn new(root: &Path) -> Self {
+        RuleSet {
+            root: root.to_path_buf(),
+            rules: Vec::new(),
+            unmatched_included: None,
+        }
+    }
+
+    /// Returns the rule set equivalent to include and exclude patterns.
+    ///
+    /// Exclude rules come after include rules so that they win, unless `include_priority` is set.
+    /// As with `--in`, paths matched by no rule are excluded when there are include patterns.
+    /// A pattern starting with `!` negates the patterns before it in its list, as in `.gitignore`
+    /// files, so `*.txt` followed by `!keep.txt` in the exclude patterns no longer excludes
+    /// `keep.txt`, while a file not matched by the include patterns stays excluded.
+    ///
+    /// # Arguments
+    ///
+    /// * `root` - The path to the root directory of the traversal.
+    /// * `include_patterns` - The gitignore-style include patterns.
+    /// * `exclude_patterns` - The gitignore-style exclude patterns.
+    /// * `include_priority` - Whether include patterns win over exclude patterns.
+    ///
+    /// # Returns
+    ///
+    /// * `Result<RuleSet>` - The rule set, or an error if a pattern is not a valid glob.
+    pub fn from_patterns(
+        root: &Path,
+        include_patterns: &[String],
+        exclude_patterns: &[String],
+        include_priority: bool,
+    ) -> Result<Self> {
+        let mut rules = RuleSet::new(root);
+        if !include_patterns.is_empty() {
+            rules.unmatched_included = Some(false);
+        }
+        let mut groups = [
+            (RuleAction::Include, include_patterns),
+            (RuleAction::Exclude, exclude_patterns),
+        ];
+        if include_priority {
+            groups.reverse();
+        }
+        for (action, patterns) in groups {
+            if !patterns.is_empty() {
+                rules.push_patterns(action, patterns)?;
+            }
+        }
+        Ok(rules)
+    }
+
+    /// Appends a rule with the given action, taking precedence over the rules before it.
+    pub fn push(&mut self, action: RuleAction, pattern: &str) -> Result<()> {
+        self.push_patterns(action, &[pattern.to_string()])
+    }
+
+    /// Appends one rule matching a list of gitignore-style patterns, in which a `!pattern` line
+    /// only cancels the matches of the patterns before it.
+    fn push_patterns(&mut self, action: RuleAction, patterns: &[String]) -> Result<()> {
+        let matcher = build_glob_matcher(&self.root, patterns)?;
+        self.rules.push((action, matcher));
+        Ok(())
+    }
+
+    /// Appends a rule written as `+pattern` to include or `-pattern` to exclude.
+    pub fn push_rule(&mut self, rule: &str) -> Result<()> {
+        let (action, pattern) = parse_rule(rule)?;
+        self.push(action, pattern)
+    }
+
+    /// Determines whether a path relative to the traversal root is included by the rules.
+    ///
+    /// A rule matching a directory also matches everything inside it.
+    ///
+    /// # Arguments
+    ///
+    /// * `relative_path` - The path relative to the traversal root.
+    /// * `is_dir` - Whether the path is a directory.
+    ///
+    /// # Returns
+    ///
+    /// * `bool` - `true` if the path should be included, `false` otherwise.
+    pub fn is_included(&self, relative_path: &Path, is_dir: bool) -> bool {
+        let included = match self.matched_action(relative_path, is_dir) {
+            Some(action) => action == RuleAction::Include,
+            None => self.unmatched_included(),
+        };
+        debug!("Path: {}, Included: {}", relative_path.display(), included);
+        included
+    }
+
+    /// Returns the action of the last rule matching a path, without building its reason.
+    ///
+    /// # Arguments
+    ///
+    /// * `relative_path` - The path relative to the traversal root.
+    /// * `is_dir` - Whether the path is a directory.
+    ///
+    /// # Returns
+    ///
+    /// * `Option<RuleAction>` - The action of the deciding rule, or `None` if no rule matches.
+    pub fn matched_action(&self, relative_path: &Path, is_dir: bool) -> Option<RuleAction> {
+        self.rules
+            .iter()
+            .rev()
+            .find(|(_, matcher)| {
+                matcher
+                    .matched_path_or_any_parents(relative_path, is_dir)
+                    .is_ignore()
+            })
+            .map(|(action, _)| *action)
+    }
+
+    /// Whether paths matched by no rule are included.
+    fn unmatched_included(&self) -> bool {
+        self.unmatched_included.unwrap_or_else(|| {
+            self.rules
+                .first()
+                .is_none_or(|(action, _)| *action == RuleAction::Exclude)
+        })
+    }
+
+    /// Determines whether a path relative to the traversal root is included, and why.
+    ///
+    /// This builds the reason for `c2p explain`; `is_included` filters paths without allocating.
+    ///
+    /// # Arguments
+    ///
+    /// * `relative_path` - The path relative to the traversal root.
+    /// * `is_dir` - Whether the path is a directory.
+    ///
+    /// # Returns
+    ///
+    /// * `(bool, FilterReason)` - Whether the path is included, and the deciding reason.
+    pub fn explain(&self, relative_path: &Path, is_dir: bool) -> (bool, FilterReason) {
+        let matching_pattern = |matcher: &Gitignore| {
+            let matched = matcher.matched_path_or_any_parents(relative_path, is_dir);
+            matched
+                .inner()
+                .filter(|_| matched.is_ignore())
+                .map(|glob| glob.original().to_string())
+        };
+        let mut matches = self
+            .rules
+            .iter()
+            .rev()
+            .filter_map(|(action, matcher)| Some((*action, matching_pattern(matcher)?)));
+
+        let Some((action, pattern)) = matches.next() else {
+            let included = self.unmatched_included();
+            let reason = if included {
+                FilterReason::Unfiltered
+            } else {
+                FilterReason::NoIncludeMatch
+            };
+            return (included, reason);
+        };
+
+        let reason = match matches.find(|(other, _)| *other != action) {
+            Some((_, overridden)) => {
+                let (include, exclude) = match action {
+                    RuleAction::Include => (pattern, overridden),
+                    RuleAction::Exclude => (overridden, pattern),
+                };
+                FilterReason::Conflict {
+                    include,
+                    exclude,
+                    winner: action,
+                }
+            }
+            None => FilterReason::Pattern { action, pattern },
+        };
+        (action == RuleAction::Include, reason)
+    }
+}
+
+/// Parses a rule written as `+pattern` to include or `-pattern` to exclude.
+///
+/// # Arguments
+///
+/// * `rule` - The rule.
+///
+/// # Returns
+///
+/// * `Result<(RuleAction, &str)>` - The action and the pattern, or an error without a `+` or `-` prefix.
+pub fn parse_rule(rule: &str) -> Result<(RuleAction, &str)> {
+    let rule = rule.trim();
+    if let Some(pattern) = rule.strip_prefix('+') {
+        Ok((RuleAction::Include, pattern))
+    } else if let Some(pattern) = rule.strip_prefix('-') {
+        Ok((RuleAction::Exclude, pattern))
+    } else {
+        Err(anyhow!(
+            "無效的規則 '{}'，規則必須以 '+' (包含) 或 '-' (排除) 開頭",
+            rule
+        ))
+    }
+}
+
+/// Parses a pattern file with one pattern per line, ignoring empty lines and `#` comments.
+///
+/// Lines starting with `!` are kept as they are and negate the patterns before them.
+///
+/// # Arguments
+///
+/// * `content` - The content of the pattern file.
+///
+/// # Returns
+///
+/// * `Vec<String>` - The patterns, in file order.
+pub fn parse_pattern_file(content: &str) -> Vec<String> {
+    content
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(String::from)
+        .collect()
+}
+
+/// Parses a pattern file into `+pattern` and `-pattern` rules, in file order.
+///
+/// Empty lines and lines starting with `#` are ignored. Lines may start with `+` or `-`; any
+/// other line is an exclude pattern, and a leading `!` negates it into an include rule, as in
+/// `.gitignore` files.
+///
+/// # Arguments
+///
+/// * `content` - The content of the pattern file.
+///
+/// # Returns
+///
+/// * `Vec<String>` - The rules.
+pub fn parse_rule_file(content: &str) -> Vec<String> {
+    parse_pattern_file(content)
+        .into_iter()
+        .map(|line| match line.strip_prefix('!') {
+            Some(pattern) => format!("+{}", pattern),
+            None if line.starts_with('+') || line.starts_with('-') => line,
+            None => format!("-{}", line),
+        })
+        .collect()
+}
+
+/// The precompiled include and exclude filters of a traversal, built once from the patterns.
+///
+/// Paths are matched relative to the traversal root without touching the filesystem.
+#[derive(Debug, Clone)]
+pub struct FilterSet {
+    mode: FilterMode,
+}
+
+#[derive(Debug, Clone)]
+enum FilterMode {
+    Glob(RuleSet),
+    Legacy {
+        root: PathBuf,
+        include: Vec<(String, Option<Regex>)>,
+        exclude: Vec<(String, Option<Regex>)>,
+        include_priority: bool,
+    },
+}
+
+impl FilterSet {
+    /// Compiles gitignore-style include and exclude patterns followed by ordered rules.
+    ///
+    /// # Arguments
+    ///
+    /// * `root` - The path to the root directory of the traversal.
+    /// * `include_patterns` - The gitignore-style include patterns.
+    /// * `exclude_patterns` - The gitignore-style exclude patterns.
+    /// * `include_priority` - Whether include patterns win over exclude patterns.
+    /// * `rules` - The `+pattern` and `-pattern` rules applied after the patterns.
+    ///
+    /// # Returns
+    ///
+    /// * `Result<FilterSet>` - The filters, or an error if a pattern or rule is invalid.
+    pub fn new(
+        root: &Path,
+        include_patterns: &[String],
+        exclude_patterns: &[String],
+        include_priority: bool,
+        rules: &[String],
+    ) -> Result<Self> {
+        let mut rule_set =
+            RuleSet::from_patterns(root, include_patterns, exclude_patterns, include_priority)?;
+        for rule in rules {
+            rule_set.push_rule(rule)?;
+        }
+        Ok(FilterSet {
+            mode: FilterMode::Glob(rule_set),
+        })
+    }
+
+    /// Compiles legacy wildcard patterns, matched unanchored against the absolute path.
+    ///
+    /// Invalid patterns are logged and never match.
+    ///
+    /// # Arguments
+    ///
+    /// * `root` - The absolute path to the root directory of the traversal.
+    /// * `include_patterns` - The wildcard include patterns.
+    /// * `exclude_patterns` - The wildcard exclude patterns.
+    /// * `include_priority` - Whether include patterns win over exclude patterns.
+    ///
+    /// # Returns
+    ///
+    /// * `FilterSet` - The filters.
+    pub fn legacy(
+        root: &Path,
+        include_patterns: &[String],
+        exclude_patterns: &[String],
+        include_priority: bool,
+    ) -> Self {
+        FilterSet {
+            mode: FilterMode::Legacy {
+                root: root.to_path_buf(),
+                include: compile_wildcards(include_patterns),
+                exclude: compile_wildcards(exclude_patterns),
+                include_priority,
+            },
+        }
+    }
+
+    /// Determines whether a path relative to the traversal root passes the filters.
+    ///
+    /// # Arguments
+    ///
+    /// * `relative_path` - The path relative to the traversal root.
+    /// * `is_dir` - Whether the path is a directory.
+    ///
+    /// # Returns
+    ///
+    /// * `bool` - `true` if the path should be included, `false` otherwise.
+    pub fn is_included(&self, relative_path: &Path, is_dir: bool) -> bool {
+        match &self.mode {
+            FilterMode::Glob(rule_set) => rule_set.is_included(relative_path, is_dir),
+            FilterMode::Legacy { root, .. } => {
+                self.matches_legacy(&root.join(relative_path).to_string_lossy())
+            }
+        }
+    }
+
+    /// Determines whether a path relative to the traversal root passes the filters, and why.
+    ///
+    /// # Arguments
+    ///
+    /// * `relative_path` - The path relative to the traversal root.
+    /// * `is_dir` - Whether the path is a directory.
+    ///
+    /// # Returns
+    ///
+    /// * `(bool, FilterReason)` - Whether the path is included, and the deciding reason.
+    pub fn explain(&self, relative_path: &Path, is_dir: bool) -> (bool, FilterReason) {
+        match &self.mode {
+            FilterMode::Glob(rule_set) => rule_set.explain(relative_path, is_dir),
+            FilterMode::Legacy { root, .. } => {
+                self.explain_legacy(&root.join(relative_path).to_string_lossy())
+            }
+        }
+    }
+
+    /// Determines whether a pattern or rule explicitly includes a path relative to the traversal
+    /// root, as opposed to including it because no pattern matches it.
+    ///
+    /// Explicitly included paths are kept even when the default exclusion profile matches them.
+    ///
+    /// # Arguments
+    ///
+    /// * `relative_path` - The path relative to the traversal root.
+    /// * `is_dir` - Whether the path is a directory.
+    ///
+    /// # Returns
+    ///
+    /// * `bool` - `true` if an include pattern or rule decides the path, `false` otherwise.
+    pub fn is_explicitly_included(&self, relative_path: &Path, is_dir: bool) -> bool {
+        match &self.mode {
+            FilterMode::Glob(rule_set) => {
+                rule_set.matched_action(relative_path, is_dir) == Some(RuleAction::Include)
+            }
+            FilterMode::Legacy { .. } => matches!(
+                self.explain(relative_path, is_dir),
+                (true, reason) if reason != FilterReason::Unfiltered
+            ),
+        }
+    }
+
+    fn matches_legacy(&self, path_str: &str) -> bool {
+        let (included, _) = self.explain_legacy(path_str);
+        debug!("Path: {}, Included: {}", path_str, included);
+        included
+    }
+
+    fn explain_legacy(&self, path_str: &str) -> (bool, FilterReason) {
+        let FilterMode::Legacy {
+            include,
+            exclude,
+            include_priority,
+            ..
+        } = &self.mode
+        else {
+            return (false, FilterReason::NoIncludeMatch);
+        };
+
+        let first_match = |patterns: &[(String, Option<Regex>)]| {
+            patterns
+                .iter()
+                .find(|(_, re)| re.as_ref().is_some_and(|re| re.is_match(path_str)))
+                .map(|(pattern, _)| pattern.clone())
+        };
+
+        // Without include patterns every path counts as included, as in earlier versions
+        let include_match = match include.is_empty() {
+            true => Some("*".to_string()),
+            false => first_match(include),
+        };
+
+        match (include_match, first_match(exclude)) {
+            (Some(_), None) if include.is_empty() => (true, FilterReason::Unfiltered),
+            (Some(_), Some(pattern)) if include.is_empty() && !*include_priority => (
+                false,
+                FilterReason::Pattern {
+                    action: RuleAction::Exclude,
+                    pattern,
+                },
+            ),
+            (Some(include), Some(exclude)) => {
+                let winner = if *include_priority {
+                    RuleAction::Include
+                } else {
+                    RuleAction::Exclude
+                };
+                (
+                    *include_priority,
+                    FilterReason::Conflict {
+                        include,
+                        exclude,
+                        winner,
+                    },
+                )
+            }
+            (Some(pattern), None) => (
+                true,
+                FilterReason::Pattern {
+                    action: RuleAction::Include,
+                    pattern,
+                },
+            ),
+            (None, _) => (false, FilterReason::NoIncludeMatch),
+        }
+    }
+}
 
 /// Determines whether a file should be included based on include and exclude patterns.
 ///
+/// This is the legacy matching behind `--legacy-patterns`: `*` and `?` are wildcards and the
+/// patterns are matched unanchored against the canonicalized absolute path. It compiles the
+/// patterns on every call; build a `FilterSet` once to filter many paths.
+///
 /// # Arguments
 ///
 /// * `path` - The path to the file to be checked.
@@ -29,49 +661,27 @@ pub fn should_include_file(
         }
     };
 
-    let included = if include_patterns.is_empty() {
-        true // 如果沒有包含模式，默認包含所有文件
-    } else {
-        include_patterns.iter().any(|pattern| {
-            let matches = matches_pattern(&path_str, pattern);
-            debug!(
-                "Include pattern '{}' matches path '{}': {}",
-                pattern, path_str, matches
-            );
-            matches
-        })
-    };
+    FilterSet::legacy(
+        Path::new(""),
+        include_patterns,
+        exclude_patterns,
+        include_priority,
+    )
+    .matches_legacy(&path_str)
+}
 
-    let excluded = exclude_patterns.iter().any(|pattern| {
-        let matches = matches_pattern(&path_str, pattern);
-        debug!(
-            "Exclude pattern '{}' matches path '{}': {}",
-            pattern, path_str, matches
-        );
-        matches
-    });
-
-    debug!(
-        "Path: {}, Included: {}, Excluded: {}, Include Priority: {}",
-        path_str, included, excluded, include_priority
-    );
-
-    match (included, excluded) {
-        (true, true) => include_priority,
-        (true, false) => true,
-        (false, true) => false,
-        (false, false) => include_patterns.is_empty(),
-    }
-}
-
-fn matches_pattern(path: &str, pattern: &str) -> bool {
-    let regex_pattern = convert_wildcard_to_regex(pattern);
-    Regex::new(&regex_pattern)
-        .map(|re| re.is_match(path))
-        .unwrap_or_else(|e| {
-            error!("無效的正則表達式 '{}': {}", regex_pattern, e);
-            false
+/// Compiles wildcard patterns, keeping invalid ones without a regex so that they never match.
+fn compile_wildcards(patterns: &[String]) -> Vec<(String, Option<Regex>)> {
+    patterns
+        .iter()
+        .map(|pattern| {
+            let regex_pattern = convert_wildcard_to_regex(pattern);
+            let re = Regex::new(&regex_pattern)
+                .map_err(|e| error!("無效的正則表達式 '{}': {}", regex_pattern, e))
+                .ok();
+            (pattern.clone(), re)
         })
+        .collect()
 }
 
 fn convert_wildcard_to_regex(pattern: &str) -> String {
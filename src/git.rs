@@ -1,9 +1,240 @@
 //! This module handles git operations.
-use anyhow::{Context, Result};
+use anyhow::{anyhow, Context, Result};
 use chrono::{DateTime, NaiveDate, Utc};
-use git2::{Commit, Diff, DiffOptions, Repository};
+use git2::{Commit, Diff, DiffOptions, Repository, Status, StatusOptions};
 use log::info;
-use std::path::Path;
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+/// A git status state used to select files with `--git-status`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GitFileStatus {
+    /// Tracked files with unstaged changes in the working tree.
+    Modified,
+    /// Files with changes staged in the index.
+    Staged,
+    /// Files not tracked by git and not ignored.
+    Untracked,
+}
+
+impl GitFileStatus {
+    fn flags(self) -> Status {
+        match self {
+            GitFileStatus::Modified => {
+                Status::WT_MODIFIED | Status::WT_RENAMED | Status::WT_TYPECHANGE
+            }
+            GitFileStatus::Staged => {
+                Status::INDEX_NEW
+                    | Status::INDEX_MODIFIED
+                    | Status::INDEX_RENAMED
+                    | Status::INDEX_TYPECHANGE
+            }
+            GitFileStatus::Untracked => Status::WT_NEW,
+        }
+    }
+}
+
+impl FromStr for GitFileStatus {
+    type Err = anyhow::Error;
+
+    fn from_str(status: &str) -> Result<Self> {
+        match status.trim().to_ascii_lowercase().as_str() {
+            "modified" => Ok(GitFileStatus::Modified),
+            "staged" => Ok(GitFileStatus::Staged),
+            "untracked" => Ok(GitFileStatus::Untracked),
+            _ => Err(anyhow!(
+                "未知的 git 狀態 '{}'，可用的狀態: modified, staged, untracked",
+                status
+            )),
+        }
+    }
+}
+
+/// Returns the files that git reports in any of the given states
+///
+/// # Arguments
+///
+/// * `root_path` - A reference to the path of the traversal root, inside a git repository
+/// * `states` - The git status states to select
+///
+/// # Returns
+///
+/// * `Result<HashSet<PathBuf>>` - The selected files relative to `root_path`, or an error
+pub fn get_files_by_status(root_path: &Path, states: &[GitFileStatus]) -> Result<HashSet<PathBuf>> {
+    info!("正在打開倉庫,路徑:{:?}", root_path);
+    let repo = Repository::discover(root_path).context("無法打開倉庫")?;
+    let flags = states
+        .iter()
+        .fold(Status::empty(), |flags, state| flags | state.flags());
+
+    let mut status_opts = StatusOptions::new();
+    status_opts
+        .include_untracked(true)
+        .recurse_untracked_dirs(true)
+        .include_ignored(false);
+    let statuses = repo
+        .statuses(Some(&mut status_opts))
+        .context("無法獲取 git 狀態")?;
+
+    let paths = statuses
+        .iter()
+        .filter(|entry| entry.status().intersects(flags))
+        .filter_map(|entry| entry.path().map(PathBuf::from));
+    relative_to_root(&repo, root_path, paths)
+}
+
+/// Returns the files whose content differs between a commit and the working tree
+///
+/// # Arguments
+///
+/// * `root_path` - A reference to the path of the traversal root, inside a git repository
+/// * `since` - A revision such as `main` or `HEAD~3`, or a date in the format `YYYY-MM-DD`,
+///   which selects the last commit on `HEAD` made before that date
+///
+/// # Returns
+///
+/// * `Result<HashSet<PathBuf>>` - The changed files relative to `root_path`, or an error
+pub fn get_files_changed_since(root_path: &Path, since: &str) -> Result<HashSet<PathBuf>> {
+    info!("正在打開倉庫,路徑:{:?}", root_path);
+    let repo = Repository::discover(root_path).context("無法打開倉庫")?;
+
+    let base_commit = match NaiveDate::parse_from_str(since, "%Y-%m-%d") {
+        Ok(date) => find_last_commit_before(&repo, date)?,
+        Err(_) => Some(
+            repo.revparse_single(since)
+                .and_then(|object| object.peel_to_commit())
+                .with_context(|| format!("無法解析修訂版本 '{}'", since))?,
+        ),
+    };
+    // Without a commit before the date, every file counts as changed
+    let base_tree = match &base_commit {
+        Some(commit) => Some(commit.tree()?),
+        None => None,
+    };
+
+    let mut diff_opts = DiffOptions::new();
+    diff_opts
+        .include_untracked(true)
+        .recurse_untracked_dirs(true);
+    let diff = repo
+        .diff_tree_to_workdir_with_index(base_tree.as_ref(), Some(&mut diff_opts))
+        .context("Failed to generate diff")?;
+
+    let paths = diff
+        .deltas()
+        .filter(|delta| delta.status() != git2::Delta::Deleted)
+        .filter_map(|delta| delta.new_file().path().map(PathBuf::from));
+    relative_to_root(&repo, root_path, paths)
+}
+
+/// Returns the time of the last commit on `HEAD` that changed each of the given files
+///
+/// # Arguments
+///
+/// * `root_path` - A reference to the path of the traversal root, inside a git repository
+/// * `paths` - The files relative to `root_path`
+///
+/// # Returns
+///
+/// * `Result<HashMap<PathBuf, i64>>` - The commit times in seconds since the Unix epoch of the
+///   files found in the history, relative to `root_path`, or an error
+pub fn get_last_commit_times(
+    root_path: &Path,
+    paths: &HashSet<PathBuf>,
+) -> Result<HashMap<PathBuf, i64>> {
+    info!("正在打開倉庫,路徑:{:?}", root_path);
+    let repo = Repository::discover(root_path).context("無法打開倉庫")?;
+    let workdir = repo
+        .workdir()
+        .ok_or_else(|| anyhow!("倉庫沒有工作目錄"))?
+        .canonicalize()?;
+    let root = root_path.canonicalize()?;
+    let prefix = root.strip_prefix(&workdir).unwrap_or(Path::new(""));
+
+    let mut times = HashMap::new();
+    let Ok(head_tree) = repo.head().and_then(|head| head.peel_to_tree()) else {
+        // A repository without commits has no history
+        return Ok(times);
+    };
+    // Only the files of HEAD were committed, so the history is not searched for the others
+    let mut pending: HashMap<PathBuf, &PathBuf> = paths
+        .iter()
+        .map(|path| (prefix.join(path), path))
+        .filter(|(repo_path, _)| head_tree.get_path(repo_path).is_ok())
+        .collect();
+
+    let mut revwalk = repo.revwalk().context("無法創建 revwalk")?;
+    revwalk.push_head()?;
+    revwalk.set_sorting(git2::Sort::TIME)?;
+
+    for oid in revwalk {
+        if pending.is_empty() {
+            break;
+        }
+        let oid = oid.context("無法從 revwalk 獲取 OID")?;
+        let commit = repo.find_commit(oid).context("無法找到提交")?;
+        let parent_tree = match commit.parents().next() {
+            Some(parent) => Some(parent.tree()?),
+            None => None,
+        };
+        let diff = repo
+            .diff_tree_to_tree(parent_tree.as_ref(), Some(&commit.tree()?), None)
+            .context("Failed to generate diff")?;
+        for delta in diff.deltas() {
+            let Some(path) = delta.new_file().path() else {
+                continue;
+            };
+            if let Some(relative_path) = pending.remove(path) {
+                times.insert(relative_path.clone(), commit.time().seconds());
+            }
+        }
+    }
+    Ok(times)
+}
+
+/// Finds the last commit on `HEAD` made before the given date
+fn find_last_commit_before(repo: &Repository, date: NaiveDate) -> Result<Option<Commit<'_>>> {
+    let mut revwalk = repo.revwalk().context("無法創建 revwalk")?;
+    revwalk.push_head().context("無法推送 HEAD 到 revwalk")?;
+    revwalk.set_sorting(git2::Sort::TIME)?;
+
+    for oid in revwalk {
+        let oid = oid.context("無法從 revwalk 獲取 OID")?;
+        let commit = repo.find_commit(oid).context("無法找到提交")?;
+        let commit_date = DateTime::<Utc>::from_timestamp(commit.time().seconds(), 0)
+            .map(|dt| dt.naive_utc().date())
+            .unwrap_or_else(|| NaiveDate::from_ymd_opt(1970, 1, 1).unwrap());
+        if commit_date < date {
+            return Ok(Some(commit));
+        }
+    }
+    Ok(None)
+}
+
+/// Converts paths relative to the repository working directory into paths relative to the
+/// traversal root, dropping those outside of it.
+fn relative_to_root(
+    repo: &Repository,
+    root_path: &Path,
+    paths: impl Iterator<Item = PathBuf>,
+) -> Result<HashSet<PathBuf>> {
+    let workdir = repo
+        .workdir()
+        .ok_or_else(|| anyhow!("倉庫沒有工作目錄"))?
+        .canonicalize()?;
+    let root = root_path.canonicalize()?;
+
+    Ok(paths
+        .filter_map(|path| {
+            workdir
+                .join(path)
+                .strip_prefix(&root)
+                .ok()
+                .map(Path::to_path_buf)
+        })
+        .collect())
+}
 
 /// Generates a git diff for the repository at the provided path
 ///
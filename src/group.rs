@@ -0,0 +1,163 @@
+//! This module groups the traversed files by directory for map-reduce prompts.
+//!
+//! Each group is rendered into its own prompt file, and an index prompt ties the parts together
+//! with the full source tree and a manifest of the part files.
+
+use crate::template::{handlebars_setup, render_template};
+use crate::tree::SourceTree;
+use anyhow::Result;
+use serde::Serialize;
+use serde_json::{json, Value};
+use std::collections::HashSet;
+use std::path::PathBuf;
+
+/// The name of the group holding the files directly inside the root directory.
+pub const ROOT_GROUP: &str = ".";
+
+/// The file name of the index prompt.
+pub const INDEX_FILE_NAME: &str = "000-index.md";
+
+const INDEX_TEMPLATE_NAME: &str = "index";
+
+/// A group of files sharing the same leading directories.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FileGroup {
+    /// The directory path of the group relative to the root, or `.` for the root directory.
+    pub name: String,
+    /// The JSON file representations of the group, in traversal order.
+    pub files: Vec<Value>,
+}
+
+impl FileGroup {
+    /// Returns the source tree containing only the files of the group.
+    ///
+    /// # Arguments
+    ///
+    /// * `tree` - The source tree of the whole project, whose ordering, depth limit, collapsing
+    ///   and annotations the group tree follows.
+    ///
+    /// # Returns
+    ///
+    /// * `String` - The rendered source tree of the group.
+    pub fn source_tree(&self, tree: &SourceTree) -> String {
+        let paths: HashSet<PathBuf> = self
+            .files
+            .iter()
+            .filter_map(|file| file["path"].as_str())
+            .map(|path| path.split('/').skip(1).collect())
+            .collect();
+        tree.render_files(&paths)
+    }
+}
+
+/// An entry of the manifest listed in the index prompt.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ManifestEntry {
+    /// The file name of the part prompt.
+    pub file: String,
+    /// The name of the group rendered into the part.
+    pub group: String,
+    /// The number of files in the part.
+    pub files: usize,
+    /// The token count of the part prompt.
+    pub tokens: usize,
+}
+
+/// Groups the files by their first `depth` directories below the root.
+///
+/// Files in shallower directories form a group of their own directory.
+///
+/// # Arguments
+///
+/// * `files` - The JSON file representations returned by `traverse_directory`.
+/// * `depth` - The number of leading directories that make up a group.
+///
+/// # Returns
+///
+/// * `Vec<FileGroup>` - The groups, ordered by their first file in traversal order.
+pub fn group_files(files: &[Value], depth: usize) -> Vec<FileGroup> {
+    let mut groups: Vec<FileGroup> = Vec::new();
+    for file in files {
+        let path = file["path"].as_str().unwrap_or("");
+        // The first component is the root directory label and the last one is the file name.
+        let components: Vec<&str> = path.split('/').skip(1).collect();
+        let directories = &components[..components.len().saturating_sub(1)];
+        let name = match directories.len().min(depth) {
+            0 => ROOT_GROUP.to_string(),
+            len => directories[..len].join("/"),
+        };
+
+        match groups.iter_mut().find(|group| group.name == name) {
+            Some(group) => group.files.push(file.clone()),
+            None => groups.push(FileGroup {
+                name,
+                files: vec![file.clone()],
+            }),
+        }
+    }
+    groups
+}
+
+/// Returns the file name of a part prompt, e.g. `003-src-core.md` for the third group `src/core`.
+///
+/// # Arguments
+///
+/// * `group` - The name of the group.
+/// * `part` - The 1-based number of the part.
+///
+/// # Returns
+///
+/// * `String` - The file name of the part prompt.
+pub fn group_file_name(group: &str, part: usize) -> String {
+    let name: String = match group {
+        ROOT_GROUP => "root".to_string(),
+        _ => group
+            .chars()
+            .map(|c| {
+                if c.is_alphanumeric() || c == '_' || c == '.' {
+                    c
+                } else {
+                    '-'
+                }
+            })
+            .collect(),
+    };
+    format!("{:03}-{}.md", part, name)
+}
+
+/// Renders the index prompt with the full source tree and the manifest of the part files.
+///
+/// # Arguments
+///
+/// * `absolute_code_path` - The label of the root directory.
+/// * `source_tree` - The source tree of the whole project.
+/// * `manifest` - The manifest entries of the part prompts.
+///
+/// # Returns
+///
+/// * `Result<String>` - The rendered index prompt.
+pub fn render_index(
+    absolute_code_path: &str,
+    source_tree: &str,
+    manifest: &[ManifestEntry],
+) -> Result<String> {
+    let handlebars = handlebars_setup(include_str!("index_template.hbs"), INDEX_TEMPLATE_NAME)?;
+    let parts: Vec<Value> = manifest
+        .iter()
+        .map(|entry| {
+            let mut part = json!(entry);
+            part["files_label"] = json!(match entry.files {
+                1 => "1 file".to_string(),
+                count => format!("{} files", count),
+            });
+            part
+        })
+        .collect();
+    let data = json!({
+        "absolute_code_path": absolute_code_path,
+        "source_tree": source_tree,
+        "part_count": manifest.len(),
+        "parts": parts,
+    });
+    render_template(&handlebars, INDEX_TEMPLATE_NAME, &data)
+}
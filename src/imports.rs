@@ -0,0 +1,420 @@
+//! This module follows the imports of source files to select the files reachable from an entry
+//! point.
+//!
+//! Rust `mod` declarations and `crate::` paths in `use` declarations and code, Python `import` and `from ... import`
+//! statements, and relative JavaScript and TypeScript `import`, `export ... from` and `require`
+//! paths are followed. Imports that resolve outside of the root are ignored.
+
+use anyhow::{anyhow, Result};
+use once_cell::sync::Lazy;
+use regex::Regex;
+use std::collections::{HashSet, VecDeque};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+static RUST_MOD: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r"(?m)^\s*(?:pub(?:\([^)]*\))?\s+)?mod\s+([A-Za-z_][A-Za-z0-9_]*)\s*;").unwrap()
+});
+static RUST_USE: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"(?m)^\s*(?:pub(?:\([^)]*\))?\s+)?use\s+([^;]+);").unwrap());
+static RUST_PATH: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"\b[A-Za-z_][A-Za-z0-9_]*(?:::[A-Za-z_][A-Za-z0-9_]*)+").unwrap());
+static RUST_ALIAS: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"\s+as\s+[A-Za-z_][A-Za-z0-9_]*").unwrap());
+static PYTHON_IMPORT: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r"(?m)^\s*import\s+([\w.]+(?:\s+as\s+\w+)?(?:\s*,\s*[\w.]+(?:\s+as\s+\w+)?)*)")
+        .unwrap()
+});
+static PYTHON_FROM_IMPORT: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"(?m)^\s*from\s+(\.*[\w.]*)\s+import\s+(\([^)]*\)|[^\n#]*)").unwrap());
+static JAVASCRIPT_IMPORT: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r#"(?:\b(?:import|export)\s[^'"`;]*?\bfrom\s*|\bimport\s*|\b(?:require|import)\s*\(\s*)['"]([^'"]+)['"]"#).unwrap()
+});
+
+/// The extensions tried, in order, for a JavaScript or TypeScript import without one.
+const JAVASCRIPT_EXTENSIONS: &[&str] = &["ts", "tsx", "d.ts", "js", "jsx", "mjs", "cjs"];
+
+/// Returns the files reachable from an entry file by following its imports.
+///
+/// # Arguments
+///
+/// * `root_path` - The path to the root directory of the traversal.
+/// * `entry` - The entry file, relative to the root or absolute.
+/// * `depth` - The maximum number of imports to follow from the entry file, or `None` for no limit.
+///
+/// # Returns
+///
+/// * `Result<HashSet<PathBuf>>` - The reachable files including the entry, relative to the root,
+///   or an error if the entry file is not a file inside the root.
+pub fn reachable_files(
+    root_path: &Path,
+    entry: &Path,
+    depth: Option<usize>,
+) -> Result<HashSet<PathBuf>> {
+    let root = root_path.canonicalize()?;
+    let entry = root
+        .join(entry)
+        .canonicalize()
+        .ok()
+        .filter(|entry| entry.is_file() && entry.starts_with(&root))
+        .ok_or_else(|| {
+            anyhow!(
+                "入口文件 '{}' 不存在或不在項目目錄 {} 中",
+                entry.display(),
+                root.display()
+            )
+        })?;
+
+    let mut reachable = HashSet::from([entry.clone()]);
+    let mut queue = VecDeque::from([(entry, 0)]);
+    while let Some((file, level)) = queue.pop_front() {
+        if depth.is_some_and(|depth| level >= depth) {
+            continue;
+        }
+        let Ok(code) = fs::read_to_string(&file) else {
+            continue;
+        };
+        for import in resolve_imports(&root, &file, &code) {
+            let Ok(import) = import.canonicalize() else {
+                continue;
+            };
+            if import.is_file() && import.starts_with(&root) && reachable.insert(import.clone()) {
+                queue.push_back((import, level + 1));
+            }
+        }
+    }
+
+    Ok(reachable
+        .into_iter()
+        .filter_map(|path| path.strip_prefix(&root).ok().map(Path::to_path_buf))
+        .collect())
+}
+
+/// Returns the existing files imported by a file, based on its extension.
+fn resolve_imports(root: &Path, file: &Path, code: &str) -> Vec<PathBuf> {
+    match file.extension().and_then(|ext| ext.to_str()).unwrap_or("") {
+        "rs" => resolve_rust_imports(file, code),
+        "py" | "pyi" => resolve_python_imports(root, file, code),
+        "js" | "jsx" | "mjs" | "cjs" | "ts" | "tsx" | "mts" | "cts" => {
+            resolve_javascript_imports(file, code)
+        }
+        _ => Vec::new(),
+    }
+}
+
+fn resolve_rust_imports(file: &Path, code: &str) -> Vec<PathBuf> {
+    let directory = file.parent().unwrap_or(Path::new(""));
+    let rust_crate = rust_crate(file);
+    let is_module_root = matches!(
+        file.file_name().and_then(|name| name.to_str()),
+        Some("main.rs" | "lib.rs" | "mod.rs")
+    ) || rust_crate
+        .as_ref()
+        .is_some_and(|rust_crate| rust_crate.roots.iter().any(|root| root == file));
+    let module_directory = match file.file_stem() {
+        Some(stem) if !is_module_root => directory.join(stem),
+        _ => directory.to_path_buf(),
+    };
+
+    let mut imports: Vec<PathBuf> = RUST_MOD
+        .captures_iter(code)
+        .filter_map(|captures| rust_module_file(&module_directory, &captures[1]))
+        .collect();
+
+    let Some(rust_crate) = rust_crate else {
+        return imports;
+    };
+    let mut paths: Vec<Vec<String>> = Vec::new();
+    for captures in RUST_USE.captures_iter(code) {
+        let tree = RUST_ALIAS.replace_all(&captures[1], "");
+        let tree: String = tree.chars().filter(|c| !c.is_whitespace()).collect();
+        paths.extend(expand_use_tree(&tree));
+    }
+    // Qualified paths such as `crate::path::label(...)` are followed like imports
+    paths.extend(
+        RUST_PATH
+            .find_iter(code)
+            .map(|found| found.as_str().split("::").map(String::from).collect()),
+    );
+
+    let library_directory = rust_crate.library.parent().unwrap_or(Path::new(""));
+    let crate_directory = rust_crate.root_directory(file);
+    for path in paths {
+        let Some((first, modules)) = path.split_first() else {
+            continue;
+        };
+        let mut directory = if first == "crate" {
+            crate_directory.to_path_buf()
+        } else if Some(first) == rust_crate.name.as_ref() {
+            // Paths through the library crate name start at the library root
+            imports.push(rust_crate.library.clone());
+            library_directory.to_path_buf()
+        } else {
+            continue;
+        };
+        for module in modules {
+            match rust_module_file(&directory, module) {
+                Some(module_file) => imports.push(module_file),
+                None => break,
+            }
+            directory = directory.join(module);
+        }
+    }
+    imports
+}
+
+/// Returns the file of a module declared in a directory, `name.rs` or `name/mod.rs`.
+fn rust_module_file(directory: &Path, name: &str) -> Option<PathBuf> {
+    [
+        directory.join(format!("{}.rs", name)),
+        directory.join(name).join("mod.rs"),
+    ]
+    .into_iter()
+    .find(|candidate| candidate.is_file())
+}
+
+/// The Cargo package containing a Rust file.
+struct RustCrate {
+    /// The name of the library, with dashes replaced by underscores.
+    name: Option<String>,
+    /// The root file of the library.
+    library: PathBuf,
+    /// The root files of the library, binaries, examples, tests, benchmarks and build script.
+    roots: Vec<PathBuf>,
+}
+
+impl RustCrate {
+    /// Returns the directory `crate::` paths of a file start from, the directory of the crate
+    /// root deepest among the ancestors of the file.
+    fn root_directory(&self, file: &Path) -> &Path {
+        self.roots
+            .iter()
+            .filter_map(|root| root.parent())
+            .filter(|directory| file.starts_with(directory))
+            .max_by_key(|directory| directory.components().count())
+            .or_else(|| self.library.parent())
+            .unwrap_or(Path::new(""))
+    }
+}
+
+/// The directories in which Cargo discovers binary, example, test and benchmark crates.
+const RUST_TARGET_DIRECTORIES: &[&str] = &["src/bin", "examples", "tests", "benches"];
+
+/// Returns the package containing a Rust file, with the roots of its crates.
+fn rust_crate(file: &Path) -> Option<RustCrate> {
+    let manifest_directory = file
+        .ancestors()
+        .skip(1)
+        .find(|directory| directory.join("Cargo.toml").is_file())?;
+    let manifest = fs::read_to_string(manifest_directory.join("Cargo.toml"))
+        .ok()
+        .and_then(|content| content.parse::<toml::Table>().ok())
+        .unwrap_or_default();
+    let target_path = |target: &toml::Value| {
+        target
+            .get("path")
+            .and_then(|path| path.as_str())
+            .map(|path| {
+                let path = manifest_directory.join(path);
+                path.canonicalize().unwrap_or(path)
+            })
+    };
+
+    let name = |section: &str| {
+        manifest
+            .get(section)
+            .and_then(|section| section.get("name"))
+            .and_then(|name| name.as_str())
+            .map(|name| name.replace('-', "_"))
+    };
+    let library = manifest
+        .get("lib")
+        .and_then(target_path)
+        .unwrap_or_else(|| manifest_directory.join("src/lib.rs"));
+
+    let mut roots = vec![
+        library.clone(),
+        manifest_directory.join("src/main.rs"),
+        manifest_directory.join("build.rs"),
+    ];
+    if let Some(build) = manifest
+        .get("package")
+        .and_then(|package| package.get("build"))
+        .and_then(|build| build.as_str())
+    {
+        roots.push(manifest_directory.join(build));
+    }
+    for directory in RUST_TARGET_DIRECTORIES {
+        let Ok(entries) = fs::read_dir(manifest_directory.join(directory)) else {
+            continue;
+        };
+        for path in entries
+            .filter_map(|entry| entry.ok())
+            .map(|entry| entry.path())
+        {
+            if path.extension().is_some_and(|extension| extension == "rs") {
+                roots.push(path);
+            } else if path.join("main.rs").is_file() {
+                roots.push(path.join("main.rs"));
+            }
+        }
+    }
+    for section in ["bin", "example", "test", "bench"] {
+        let targets = manifest.get(section).and_then(|targets| targets.as_array());
+        roots.extend(targets.into_iter().flatten().filter_map(target_path));
+    }
+
+    Some(RustCrate {
+        name: name("lib").or_else(|| name("package")),
+        library,
+        roots,
+    })
+}
+
+/// Expands a `use` tree without whitespace, such as `crate::a::{b,c::{d,self}}`, into its paths.
+fn expand_use_tree(tree: &str) -> Vec<Vec<String>> {
+    let Some(open) = tree.find('{') else {
+        let path: Vec<String> = tree
+            .split("::")
+            .filter(|segment| !segment.is_empty() && *segment != "self" && *segment != "*")
+            .map(String::from)
+            .collect();
+        return vec![path];
+    };
+    let prefix: Vec<String> = tree[..open]
+        .split("::")
+        .filter(|segment| !segment.is_empty())
+        .map(String::from)
+        .collect();
+    let inner = tree[open + 1..]
+        .strip_suffix('}')
+        .unwrap_or(&tree[open + 1..]);
+
+    let mut items = Vec::new();
+    let mut nesting = 0;
+    let mut start = 0;
+    for (index, c) in inner.char_indices() {
+        match c {
+            '{' => nesting += 1,
+            '}' => nesting -= 1,
+            ',' if nesting == 0 => {
+                items.push(&inner[start..index]);
+                start = index + 1;
+            }
+            _ => {}
+        }
+    }
+    items.push(&inner[start..]);
+
+    let mut paths = vec![prefix.clone()];
+    for item in items.into_iter().filter(|item| !item.is_empty()) {
+        for path in expand_use_tree(item) {
+            paths.push(prefix.iter().cloned().chain(path).collect());
+        }
+    }
+    paths
+}
+
+fn resolve_python_imports(root: &Path, file: &Path, code: &str) -> Vec<PathBuf> {
+    let directory = file.parent().unwrap_or(Path::new(""));
+    let bases = [root, directory];
+    let mut imports = Vec::new();
+
+    for captures in PYTHON_IMPORT.captures_iter(code) {
+        for module in captures[1].split(',') {
+            let module = module.split_whitespace().next().unwrap_or("");
+            for base in bases {
+                imports.extend(python_module_files(base, module));
+            }
+        }
+    }
+
+    for captures in PYTHON_FROM_IMPORT.captures_iter(code) {
+        let module = &captures[1];
+        let names: Vec<&str> = captures[2]
+            .trim_matches(|c: char| c == '(' || c == ')' || c.is_whitespace())
+            .split(',')
+            .filter_map(|name| name.split_whitespace().next())
+            .filter(|name| *name != "*")
+            .collect();
+
+        let relative_module = module.trim_start_matches('.');
+        let level = module.len() - relative_module.len();
+        let module_bases: Vec<PathBuf> = if level > 0 {
+            directory
+                .ancestors()
+                .nth(level - 1)
+                .map(Path::to_path_buf)
+                .into_iter()
+                .collect()
+        } else {
+            bases.iter().map(|base| base.to_path_buf()).collect()
+        };
+
+        for base in module_bases {
+            imports.extend(python_module_files(&base, relative_module));
+            // The imported names may be submodules of the package
+            let package = base.join(relative_module.replace('.', "/"));
+            for name in &names {
+                imports.extend(python_module_files(&package, name));
+            }
+        }
+    }
+    imports
+}
+
+/// Returns the files of a dotted Python module below a base directory, including the
+/// `__init__.py` files of its packages.
+fn python_module_files(base: &Path, module: &str) -> Vec<PathBuf> {
+    let mut files = Vec::new();
+    let mut directory = base.to_path_buf();
+    for name in module.split('.').filter(|name| !name.is_empty()) {
+        let module_file = directory.join(format!("{}.py", name));
+        directory = directory.join(name);
+        let package_file = directory.join("__init__.py");
+        if module_file.is_file() {
+            files.push(module_file);
+        } else if package_file.is_file() {
+            files.push(package_file);
+        } else {
+            break;
+        }
+    }
+    files
+}
+
+fn resolve_javascript_imports(file: &Path, code: &str) -> Vec<PathBuf> {
+    let directory = file.parent().unwrap_or(Path::new(""));
+    JAVASCRIPT_IMPORT
+        .captures_iter(code)
+        .map(|captures| captures[1].to_string())
+        .filter(|specifier| specifier.starts_with("./") || specifier.starts_with("../"))
+        .filter_map(|specifier| javascript_module_file(&directory.join(specifier)))
+        .collect()
+}
+
+/// Resolves a relative JavaScript or TypeScript import to a file, trying the path itself, the
+/// path with each known extension, TypeScript sources for `.js` paths and `index` files.
+fn javascript_module_file(path: &Path) -> Option<PathBuf> {
+    if path.is_file() {
+        return Some(path.to_path_buf());
+    }
+    let with_extension = |path: &Path| {
+        JAVASCRIPT_EXTENSIONS
+            .iter()
+            .map(|extension| {
+                let mut candidate = path.as_os_str().to_owned();
+                candidate.push(".");
+                candidate.push(extension);
+                PathBuf::from(candidate)
+            })
+            .find(|candidate| candidate.is_file())
+    };
+    let typescript_source = || match path.extension().and_then(|ext| ext.to_str()) {
+        Some("js" | "jsx" | "mjs" | "cjs") => with_extension(&path.with_extension("")),
+        _ => None,
+    };
+    with_extension(path)
+        .or_else(typescript_source)
+        .or_else(|| with_extension(&path.join("index")))
+}
@@ -0,0 +1,191 @@
+//! This module maps file extensions and well-known file names to programming languages.
+
+use anyhow::{anyhow, Result};
+use std::path::Path;
+
+/// Well-known file names and the language of their content.
+const FILE_NAMES: &[(&str, &str)] = &[
+    ("Dockerfile", "dockerfile"),
+    ("Containerfile", "dockerfile"),
+    ("Makefile", "makefile"),
+    ("GNUmakefile", "makefile"),
+    ("makefile", "makefile"),
+    ("CMakeLists.txt", "cmake"),
+    ("Cargo.lock", "toml"),
+    ("Gemfile", "ruby"),
+    ("Rakefile", "ruby"),
+    ("Jenkinsfile", "groovy"),
+    ("Vagrantfile", "ruby"),
+    ("BUILD", "starlark"),
+    ("WORKSPACE", "starlark"),
+    (".bashrc", "shell"),
+    (".zshrc", "shell"),
+    (".profile", "shell"),
+];
+
+/// File names whose variants, such as `Dockerfile.dev` or `Makefile.linux`, keep their language.
+const VARIANT_FILE_NAMES: &[&str] = &[
+    "Dockerfile",
+    "Containerfile",
+    "Makefile",
+    "GNUmakefile",
+    "makefile",
+];
+
+/// File extensions and the language of their content.
+const EXTENSIONS: &[(&str, &str)] = &[
+    ("rs", "rust"),
+    ("py", "python"),
+    ("pyi", "python"),
+    ("js", "javascript"),
+    ("jsx", "javascript"),
+    ("mjs", "javascript"),
+    ("cjs", "javascript"),
+    ("ts", "typescript"),
+    ("tsx", "typescript"),
+    ("mts", "typescript"),
+    ("go", "go"),
+    ("java", "java"),
+    ("kt", "kotlin"),
+    ("kts", "kotlin"),
+    ("scala", "scala"),
+    ("groovy", "groovy"),
+    ("gradle", "groovy"),
+    ("c", "c"),
+    ("h", "c"),
+    ("cc", "cpp"),
+    ("cpp", "cpp"),
+    ("cxx", "cpp"),
+    ("hpp", "cpp"),
+    ("hh", "cpp"),
+    ("hxx", "cpp"),
+    ("cs", "csharp"),
+    ("fs", "fsharp"),
+    ("swift", "swift"),
+    ("m", "objective-c"),
+    ("mm", "objective-c"),
+    ("rb", "ruby"),
+    ("php", "php"),
+    ("pl", "perl"),
+    ("pm", "perl"),
+    ("lua", "lua"),
+    ("r", "r"),
+    ("dart", "dart"),
+    ("ex", "elixir"),
+    ("exs", "elixir"),
+    ("erl", "erlang"),
+    ("hrl", "erlang"),
+    ("hs", "haskell"),
+    ("ml", "ocaml"),
+    ("mli", "ocaml"),
+    ("clj", "clojure"),
+    ("cljs", "clojure"),
+    ("zig", "zig"),
+    ("nim", "nim"),
+    ("jl", "julia"),
+    ("sh", "shell"),
+    ("bash", "shell"),
+    ("zsh", "shell"),
+    ("fish", "shell"),
+    ("ps1", "powershell"),
+    ("bat", "batch"),
+    ("cmd", "batch"),
+    ("sql", "sql"),
+    ("html", "html"),
+    ("htm", "html"),
+    ("css", "css"),
+    ("scss", "scss"),
+    ("sass", "scss"),
+    ("less", "less"),
+    ("vue", "vue"),
+    ("svelte", "svelte"),
+    ("hbs", "handlebars"),
+    ("json", "json"),
+    ("jsonc", "json"),
+    ("yaml", "yaml"),
+    ("yml", "yaml"),
+    ("toml", "toml"),
+    ("xml", "xml"),
+    ("ini", "ini"),
+    ("cfg", "ini"),
+    ("proto", "protobuf"),
+    ("graphql", "graphql"),
+    ("gql", "graphql"),
+    ("tf", "terraform"),
+    ("cmake", "cmake"),
+    ("mk", "makefile"),
+    ("dockerfile", "dockerfile"),
+    ("md", "markdown"),
+    ("markdown", "markdown"),
+    ("rst", "restructuredtext"),
+    ("tex", "latex"),
+    ("txt", "text"),
+];
+
+/// Detects the language of a file from its name, then from its extension.
+///
+/// # Arguments
+///
+/// * `path` - The path to the file.
+///
+/// # Returns
+///
+/// * `Option<&'static str>` - The language, or `None` if it is not in the table.
+pub fn detect_language(path: &Path) -> Option<&'static str> {
+    let file_name = path.file_name()?.to_str()?;
+    if let Some((_, language)) = FILE_NAMES.iter().find(|(name, _)| *name == file_name) {
+        return Some(language);
+    }
+    // Variants such as `Dockerfile.dev` keep the language of their base name
+    if let Some((_, language)) = FILE_NAMES.iter().find(|(name, _)| {
+        VARIANT_FILE_NAMES.contains(name) && file_name.starts_with(&format!("{}.", name))
+    }) {
+        return Some(language);
+    }
+
+    let extension = path.extension()?.to_str()?.to_ascii_lowercase();
+    EXTENSIONS
+        .iter()
+        .find(|(ext, _)| *ext == extension)
+        .map(|(_, language)| *language)
+}
+
+/// Returns every language of the table, sorted and without duplicates.
+pub fn known_languages() -> Vec<&'static str> {
+    let mut languages: Vec<&str> = FILE_NAMES
+        .iter()
+        .chain(EXTENSIONS)
+        .map(|(_, language)| *language)
+        .collect();
+    languages.sort_unstable();
+    languages.dedup();
+    languages
+}
+
+/// Normalizes language names given by the user, checking them against the table.
+///
+/// # Arguments
+///
+/// * `languages` - The language names, in any case.
+///
+/// # Returns
+///
+/// * `Result<Vec<String>>` - The lowercase names, or an error naming an unknown language.
+pub fn parse_languages(languages: &[String]) -> Result<Vec<String>> {
+    let known = known_languages();
+    languages
+        .iter()
+        .map(|language| {
+            let language = language.trim().to_ascii_lowercase();
+            if known.contains(&language.as_str()) {
+                Ok(language)
+            } else {
+                Err(anyhow!(
+                    "未知的語言 '{}'，可用的語言: {}",
+                    language,
+                    known.join(", ")
+                ))
+            }
+        })
+        .collect()
+}
@@ -1,13 +1,26 @@
+pub mod budget;
+pub mod chunk;
+pub mod explain;
 pub mod filter;
 pub mod git;
+pub mod group;
+pub mod imports;
+pub mod language;
+pub mod model;
 pub mod path;
+pub mod sort;
+pub mod split;
 pub mod template;
 pub mod token;
+pub mod tree;
 
-pub use filter::should_include_file;
+pub use budget::fit_to_budget;
+pub use explain::{explain_directory, Explanation};
+pub use filter::{should_include_file, FilterSet};
 pub use git::{get_git_diff, get_git_diff_between_branches};
+pub use model::{CostEstimate, ModelInfo, ModelRegistry};
 pub use path::{label, traverse_directory};
 pub use template::{
     copy_to_clipboard, handle_undefined_variables, handlebars_setup, render_template, write_to_file,
 };
-pub use token::{count_tokens, get_model_info, get_tokenizer};
+pub use token::{count_tokens, get_model_info, get_tokenizer, load_tokenizer, Tokenizer};
@@ -5,10 +5,11 @@ use colored::*;
 use env_logger::Builder;
 use git2::Repository;
 use handlebars::Handlebars;
-use inquire::{Select, Text};
+use inquire::{Confirm, Select, Text};
 use log::LevelFilter;
 use regex::Regex;
 use serde_json::json;
+use std::collections::HashSet;
 use std::fs;
 use std::io::Write;
 use std::path::{Path, PathBuf};
@@ -116,11 +117,7 @@ const TEMPLATES: &[(&str, &str, &str)] = &[
 ];
 
 #[derive(Parser)]
-#[clap(
-    name = "c2p",
-    version = "2.3.1",
-    author = "Mufeed VH & Olivier D & Jerome Leong"
-)]
+#[clap(name = "c2p", version, author = "Mufeed VH & Olivier D & Jerome Leong")]
 struct Cli {
     #[clap(subcommand)]
     command: Commands,
@@ -138,36 +135,222 @@ enum Commands {
         #[clap(flatten)]
         args: Args,
     },
+    /// Show for every file whether it is included and why
+    Explain {
+        path: PathBuf,
+        #[clap(flatten)]
+        filter: FilterArgs,
+    },
 }
 
 #[derive(Parser)]
-struct Args {
-    /// Patterns to include
+struct FilterArgs {
+    /// Gitignore-style glob patterns to include, relative to the project root
     #[clap(short, long = "in", visible_alias = "include")]
     include: Option<String>,
 
-    /// Patterns to exclude
+    /// Gitignore-style glob patterns to exclude, relative to the project root
     #[clap(short, long = "nor", visible_alias = "exclude")]
     exclude: Option<String>,
 
+    /// File with gitignore-style include patterns, one per line; '!pattern' negates the patterns before it
+    #[clap(long, value_name = "FILE")]
+    in_file: Vec<PathBuf>,
+
+    /// File with gitignore-style exclude patterns, one per line; '!pattern' negates the patterns before it
+    #[clap(long, value_name = "FILE")]
+    nor_file: Vec<PathBuf>,
+
     /// Include files in case of conflict between include and exclude patterns
     #[clap(long)]
     include_priority: bool,
 
+    /// Include hidden files and directories, such as .github
+    #[clap(long)]
+    hidden: bool,
+
+    /// Disregard .gitignore, .ignore and .c2pignore files
+    #[clap(long)]
+    no_ignore: bool,
+
+    /// Apply .gitignore files even outside of git repositories
+    #[clap(long)]
+    ignore_without_git: bool,
+
+    /// Follow symbolic links
+    #[clap(long)]
+    follow_symlinks: bool,
+
+    /// Disable the default exclusion of lockfiles, node_modules, vendor, dist, minified assets, source maps and generated files
+    #[clap(long)]
+    no_default_excludes: bool,
+
+    /// Maximum depth of the traversal below the project root
+    #[clap(long, value_name = "N")]
+    max_depth: Option<usize>,
+
+    /// Ordered include (+pattern) or exclude (-pattern) rule; the last matching rule wins
+    ///
+    /// Rules are applied after --in/--nor, e.g. --rule '+src/**' --rule '-src/generated/**' --rule '+src/generated/api.rs'
+    #[clap(
+        long = "rule",
+        value_name = "RULE",
+        allow_hyphen_values = true,
+        conflicts_with = "legacy_patterns"
+    )]
+    rules: Vec<String>,
+
+    /// File with one rule per line, applied before --rule; bare patterns exclude and '!pattern' includes
+    #[clap(long, value_name = "FILE", conflicts_with = "legacy_patterns")]
+    rule_file: Vec<PathBuf>,
+
+    /// Match --in/--nor as unanchored wildcards against the absolute path, as in earlier versions; '!pattern' lines of pattern files are rejected
+    #[clap(long)]
+    legacy_patterns: bool,
+}
+
+#[derive(Parser)]
+struct Args {
+    #[clap(flatten)]
+    filter: FilterArgs,
+
     /// Exclude files/folders from the source tree based on exclude patterns
     #[clap(long)]
     exclude_from_tree: bool,
 
+    /// Only include files whose content matches this regular expression
+    #[clap(long, value_name = "REGEX")]
+    grep: Option<Regex>,
+
+    /// Keep only the regions matching --grep, with this many lines of context around each match
+    #[clap(long, value_name = "N", requires = "grep")]
+    grep_context: Option<usize>,
+
+    /// Only include files in these comma-separated languages, e.g. rust,python
+    #[clap(long, value_name = "LANGUAGES")]
+    only_lang: Option<String>,
+
+    /// Leave out files in these comma-separated languages
+    #[clap(long, value_name = "LANGUAGES")]
+    skip_lang: Option<String>,
+
+    /// Only include files in these comma-separated git states: modified, staged, untracked
+    #[clap(long, value_name = "STATES")]
+    git_status: Option<String>,
+
+    /// Only include the definition of this function, struct, enum, trait, class or impl block, with line numbers
+    ///
+    /// Can be repeated; files defining none of the symbols are left out
+    #[clap(long = "symbol", value_name = "NAME", conflicts_with = "grep_context")]
+    symbols: Vec<String>,
+
+    /// Only include the files reachable from this entry file by following its imports
+    ///
+    /// Follows Rust mod and use crate:: paths, Python imports and relative JavaScript/TypeScript imports and requires
+    #[clap(long, value_name = "FILE")]
+    from: Option<PathBuf>,
+
+    /// The maximum number of imports to follow from the --from entry file
+    #[clap(long, value_name = "N", requires = "from")]
+    depth: Option<usize>,
+
+    /// Only include files changed between a revision or date (YYYY-MM-DD) and the working tree
+    #[clap(long, value_name = "REV|DATE")]
+    changed_since: Option<String>,
+
+    /// Sort the files and the entries of each tree level by: name, path(default), size, mtime, tokens, git-recency
+    ///
+    /// Directories are sorted by the total size and tokens and the latest mtime and commit of their contents
+    #[clap(long, value_name = "KEY", default_value = "path")]
+    sort: c2p::sort::SortKey,
+
+    /// The direction of --sort: asc(default) or desc
+    #[clap(long, value_name = "ORDER", default_value = "asc")]
+    sort_order: c2p::sort::SortOrder,
+
+    /// Mix directories with files in the source tree instead of listing them first
+    #[clap(long)]
+    no_dirs_first: bool,
+
+    /// Collapse the contents of the directories deeper than this in the source tree into a "(+N files)" line
+    #[clap(long, value_name = "N")]
+    tree_depth: Option<usize>,
+
+    /// Collapse the contents of the directories with more than this many entries in the source tree into a "(+N files)" line
+    #[clap(long, value_name = "N")]
+    tree_collapse: Option<usize>,
+
+    /// Annotate the source tree entries with these comma-separated values: size, lines, tokens
+    ///
+    /// Directories show the totals of their contents; lines and tokens count the files in the prompt
+    #[clap(long, value_name = "VALUES")]
+    tree_annotate: Option<String>,
+
     /// Optional tokenizer to use for token count
     ///
     /// Supported tokenizers: o200k(default), cl100k, p50k, p50k_edit, r50k, gpt2
     #[clap(short = 'c', long)]
     encoding: Option<String>,
 
+    /// Optional path to a HuggingFace tokenizer.json file, used instead of the encoding
+    #[clap(long)]
+    tokenizer_file: Option<PathBuf>,
+
+    /// Optional model from the model registry, used instead of the encoding
+    ///
+    /// The model sets the encoding and the context window the prompt is checked against
+    #[clap(short, long, conflicts_with = "encoding")]
+    model: Option<String>,
+
+    /// Optional TOML file extending the built-in model registry (default: ~/.c2p/models.toml)
+    #[clap(long)]
+    models_file: Option<PathBuf>,
+
+    /// Optional comma-separated models to compare the estimated input cost with
+    #[clap(long)]
+    compare_models: Option<String>,
+
+    /// Fail instead of warning when the prompt exceeds the model's context window
+    #[clap(long, requires = "model")]
+    fail_on_overflow: bool,
+
     /// Optional output file path
     #[clap(short, long)]
     output: Option<String>,
 
+    /// Split the prompt into numbered parts of at most this many tokens each
+    ///
+    /// Parts are written to numbered output files (e.g. prompt.001.md) or copied to the clipboard one at a time;
+    /// with --no-clipboard and no --output, the parts are not written anywhere
+    #[clap(long, value_name = "TOKENS", conflicts_with = "json")]
+    split: Option<usize>,
+
+    /// Write one prompt per directory group plus an index prompt into this directory
+    ///
+    /// Files are grouped by their top-level directory, or by the number of directories given by --group-depth; cannot be combined with --output
+    #[clap(long, value_name = "DIR", conflicts_with_all = ["split", "json", "output"])]
+    map_reduce: Option<PathBuf>,
+
+    /// The number of leading directories that make up a group in map-reduce mode
+    #[clap(long, value_name = "N", default_value_t = 1, requires = "map_reduce")]
+    group_depth: usize,
+
+    /// Optional token budget; files are truncated, elided or dropped until the prompt fits
+    #[clap(long)]
+    max_tokens: Option<usize>,
+
+    /// Skip the content of files larger than this size (e.g. 500k, 10M), listing them with a placeholder
+    #[clap(long, value_name = "SIZE", value_parser = c2p::path::parse_size)]
+    max_file_size: Option<u64>,
+
+    /// Abort when the included files exceed this total size (e.g. 50M)
+    #[clap(long, value_name = "SIZE", value_parser = c2p::path::parse_size)]
+    max_total_size: Option<u64>,
+
+    /// Optional per-file token limit; larger files are split into partial chunks at function, impl or class boundaries
+    #[clap(long, value_name = "TOKENS")]
+    max_file_tokens: Option<usize>,
+
     /// Add line numbers to the source code
     #[clap(short = 'n', long)]
     line_number: bool,
@@ -188,6 +371,10 @@ struct Args {
     #[clap(long)]
     hbs: Option<PathBuf>,
 
+    /// Print a table of the files and directories with the most tokens
+    #[clap(long, value_name = "N", num_args = 0..=1, default_missing_value = "10")]
+    token_report: Option<usize>,
+
     /// Print output as JSON
     #[clap(long)]
     json: bool,
@@ -211,6 +398,9 @@ fn main() -> Result<()> {
         Commands::Path { path, args } => {
             process_path(path, args)?;
         }
+        Commands::Explain { path, filter } => {
+            explain_path(path, filter)?;
+        }
     }
 
     Ok(())
@@ -238,22 +428,56 @@ fn process_path(path: &Path, args: &Args) -> Result<()> {
 
     log::info!("遍歷目錄並構建樹...");
 
-    let include_patterns = parse_patterns(&args.include);
-    let exclude_patterns = parse_patterns(&args.exclude);
-
-    let (tree, files) = c2p::path::traverse_directory(
-        path,
-        &include_patterns,
-        &exclude_patterns,
-        args.include_priority,
-        args.line_number,
-        args.exclude_from_tree,
-        args.no_codeblock,
-    )
-    .map_err(|e| {
-        log::error!("失敗!");
-        anyhow::anyhow!("無法構建目錄樹: {}", e)
-    })?;
+    let traverse_options = c2p::path::TraverseOptions {
+        line_number: args.line_number,
+        exclude_from_tree: args.exclude_from_tree,
+        no_codeblock: args.no_codeblock,
+        max_file_tokens: args.max_file_tokens,
+        max_file_size: args.max_file_size,
+        max_total_size: args.max_total_size,
+        only_files: select_files(path, args)?,
+        only_languages: c2p::language::parse_languages(&parse_patterns(&args.only_lang))?,
+        skip_languages: c2p::language::parse_languages(&parse_patterns(&args.skip_lang))?,
+        grep: args.grep.clone(),
+        grep_context: args.grep_context,
+        symbols: args.symbols.clone(),
+        sort: args.sort,
+        sort_order: args.sort_order,
+        no_dirs_first: args.no_dirs_first,
+        tree: c2p::tree::TreeOptions {
+            max_depth: args.tree_depth,
+            collapse: args.tree_collapse,
+            annotations: parse_patterns(&args.tree_annotate)
+                .iter()
+                .map(|annotation| annotation.parse())
+                .collect::<Result<_>>()?,
+        },
+        ..filter_options(&args.filter)?
+    };
+    let registry = c2p::model::ModelRegistry::load(args.models_file.as_deref())?;
+    let model = match &args.model {
+        Some(name) => Some(registry.get(name)?.clone()),
+        None => None,
+    };
+    let encoding = match &model {
+        Some(model) => Some(model.encoding.clone()),
+        None => args.encoding.clone(),
+    };
+    let tokenizer_file = args.tokenizer_file.clone().or_else(|| {
+        model
+            .as_ref()
+            .and_then(|model| model.tokenizer_file.clone())
+    });
+    let tokenizer = c2p::token::load_tokenizer(&encoding, &tokenizer_file)?;
+
+    let (source_tree, mut files) =
+        c2p::path::traverse_directory_tree(path, &traverse_options, tokenizer.as_ref()).map_err(
+            |e| {
+                log::error!("失敗!");
+                anyhow::anyhow!("無法構建目錄樹: {}", e)
+            },
+        )?;
+    let tree = source_tree.render();
 
     let git_diff = if template_contains_variables(&template_content, &["git_diff"]) {
         log::info!("生成 git diff...");
@@ -282,8 +506,8 @@ fn process_path(path: &Path, args: &Args) -> Result<()> {
 
     let mut data = json!({
         "absolute_code_path": c2p::path::label(path),
-        "source_tree": tree,
-        "files": files,
+        "source_tree": &tree,
+        "files": &files,
         "git_diff": git_diff,
         "git_diff_branch": git_diff_branch,
         "git_log_date": git_log_date
@@ -299,31 +523,118 @@ fn process_path(path: &Path, args: &Args) -> Result<()> {
         select_language()?
     };
 
-    let mut rendered = render_template(&handlebars, &template_name, &data)?;
+    let mut render_prompt = |files: &[serde_json::Value], source_tree: &str| -> Result<String> {
+        data["files"] = json!(files);
+        data["source_tree"] = json!(source_tree);
+        let mut rendered = render_template(&handlebars, &template_name, &data)?;
+        if !lang.is_empty() {
+            rendered.push_str(&format!("\nYou must use {} language to reply", lang));
+        }
+        Ok(rendered)
+    };
+
+    let model_info = match (&model, &tokenizer_file) {
+        (Some(model), None) => format!(
+            "{} (上下文窗口: {} tokens)",
+            model.name, model.context_window
+        ),
+        (Some(model), Some(tokenizer_file)) => format!(
+            "{} (上下文窗口: {} tokens, HuggingFace tokenizer {})",
+            model.name,
+            model.context_window,
+            tokenizer_file.display()
+        ),
+        (None, Some(tokenizer_file)) => {
+            format!("HuggingFace tokenizer {}", tokenizer_file.display())
+        }
+        (None, None) => c2p::token::get_model_info(&encoding),
+    };
 
-    if !lang.is_empty() {
-        rendered.push_str(&format!("\nYou must use {} language to reply", lang));
+    if let Some(output_dir) = &args.map_reduce {
+        let (rendered, token_count) = output_map_reduce(
+            output_dir,
+            path,
+            &source_tree,
+            &files,
+            args,
+            model.as_ref(),
+            tokenizer.as_ref(),
+            render_prompt,
+        )?;
+        let costs = estimate_costs(
+            &registry,
+            model.as_ref(),
+            token_count,
+            &rendered,
+            &parse_patterns(&args.compare_models),
+        )?;
+        print_normal_output(token_count, &model_info, &costs);
+        if let Some(limit) = args.token_report {
+            c2p::token::print_token_report(&files, limit);
+        }
+        return Ok(());
     }
 
-    let token_count = {
-        let bpe = c2p::token::get_tokenizer(&args.encoding);
-        bpe.encode_with_special_tokens(&rendered).len()
+    let (rendered, token_count) = if let Some(max_tokens) = args.max_tokens {
+        let (rendered, token_count, cuts) =
+            c2p::budget::fit_to_budget(&mut files, max_tokens, tokenizer.as_ref(), |files| {
+                render_prompt(files, &tree)
+            })?;
+        print_budget_report(max_tokens, &cuts);
+        (rendered, token_count)
+    } else {
+        let rendered = render_prompt(&files, &tree)?;
+        let token_count = tokenizer.count(&rendered);
+        (rendered, token_count)
     };
 
-    let paths: Vec<String> = files
-        .iter()
-        .filter_map(|file| file.get("path").and_then(|p| p.as_str()).map(String::from))
-        .collect();
+    let paths = c2p::path::unique_paths(&files);
 
-    let model_info = c2p::token::get_model_info(&args.encoding);
+    let costs = estimate_costs(
+        &registry,
+        model.as_ref(),
+        token_count,
+        &rendered,
+        &parse_patterns(&args.compare_models),
+    )?;
 
     let rendered = if args.json {
-        print_json_output(&rendered, path, token_count, model_info, &paths)?
+        print_json_output(&rendered, path, token_count, &model_info, &costs, &paths)?
     } else {
         rendered
     };
 
-    print_normal_output(token_count, model_info);
+    print_normal_output(token_count, &model_info, &costs);
+
+    let parts = match args.split {
+        Some(split_tokens) => Some(c2p::split::split_prompt(
+            &rendered,
+            &files,
+            split_tokens,
+            tokenizer.as_ref(),
+        )?),
+        None => None,
+    };
+
+    // A split prompt is sent part by part, so each part is checked against the context window
+    if let Some(model) = &model {
+        match &parts {
+            Some(parts) => {
+                for part in parts {
+                    check_context_window(model, tokenizer.count(part), args.fail_on_overflow)?;
+                }
+            }
+            None => check_context_window(model, token_count, args.fail_on_overflow)?,
+        }
+    }
+
+    if let Some(limit) = args.token_report {
+        c2p::token::print_token_report(&files, limit);
+    }
+
+    if let (Some(split_tokens), Some(parts)) = (args.split, &parts) {
+        return output_parts(parts, split_tokens, args);
+    }
 
     if !args.no_clipboard {
         copy_to_clipboard_with_feedback(&rendered);
@@ -357,6 +668,126 @@ fn parse_patterns(patterns: &Option<String>) -> Vec<String> {
         .unwrap_or_default()
 }
 
+fn select_files(path: &Path, args: &Args) -> Result<Option<HashSet<PathBuf>>> {
+    let mut selected: Option<HashSet<PathBuf>> = None;
+
+    if args.git_status.is_some() {
+        let states = parse_patterns(&args.git_status)
+            .iter()
+            .map(|state| state.parse())
+            .collect::<Result<Vec<c2p::git::GitFileStatus>>>()?;
+        log::info!("根據 git 狀態選擇文件...");
+        selected = Some(c2p::git::get_files_by_status(path, &states)?);
+    }
+
+    if let Some(since) = &args.changed_since {
+        log::info!("選擇自 {} 以來更改的文件...", since);
+        let changed = c2p::git::get_files_changed_since(path, since)?;
+        selected = Some(match selected {
+            Some(selected) => selected.intersection(&changed).cloned().collect(),
+            None => changed,
+        });
+    }
+
+    if let Some(entry) = &args.from {
+        log::info!("從 {} 跟隨導入選擇文件...", entry.display());
+        let reachable = c2p::imports::reachable_files(path, entry, args.depth)?;
+        selected = Some(match selected {
+            Some(selected) => selected.intersection(&reachable).cloned().collect(),
+            None => reachable,
+        });
+    }
+
+    Ok(selected)
+}
+
+fn read_patterns(
+    patterns: &Option<String>,
+    pattern_files: &[PathBuf],
+    legacy_patterns: bool,
+) -> Result<Vec<String>> {
+    let mut patterns = parse_patterns(patterns);
+    for pattern_file in pattern_files {
+        let content = fs::read_to_string(pattern_file)
+            .with_context(|| format!("無法讀取模式文件: {:?}", pattern_file))?;
+        let file_patterns = c2p::filter::parse_pattern_file(&content);
+        // Legacy wildcards cannot express negations, so '!' lines are rejected instead of being
+        // matched literally
+        if let Some(negation) = file_patterns
+            .iter()
+            .find(|pattern| legacy_patterns && pattern.starts_with('!'))
+        {
+            return Err(anyhow::anyhow!(
+                "模式文件 {:?} 中的否定模式 '{}' 不支持 --legacy-patterns",
+                pattern_file,
+                negation
+            ));
+        }
+        patterns.extend(file_patterns);
+    }
+    Ok(patterns)
+}
+
+fn read_rules(filter: &FilterArgs) -> Result<Vec<String>> {
+    let mut rules = Vec::new();
+    for rule_file in &filter.rule_file {
+        let content = fs::read_to_string(rule_file)
+            .with_context(|| format!("無法讀取規則文件: {:?}", rule_file))?;
+        rules.extend(c2p::filter::parse_rule_file(&content));
+    }
+    rules.extend(filter.rules.iter().cloned());
+    Ok(rules)
+}
+
+fn filter_options(filter: &FilterArgs) -> Result<c2p::path::TraverseOptions> {
+    Ok(c2p::path::TraverseOptions {
+        include: read_patterns(&filter.include, &filter.in_file, filter.legacy_patterns)?,
+        exclude: read_patterns(&filter.exclude, &filter.nor_file, filter.legacy_patterns)?,
+        include_priority: filter.include_priority,
+        legacy_patterns: filter.legacy_patterns,
+        rules: read_rules(filter)?,
+        hidden: filter.hidden,
+        no_ignore: filter.no_ignore,
+        ignore_without_git: filter.ignore_without_git,
+        follow_symlinks: filter.follow_symlinks,
+        max_depth: filter.max_depth,
+        no_default_excludes: filter.no_default_excludes,
+        ..Default::default()
+    })
+}
+
+fn explain_path(path: &Path, filter: &FilterArgs) -> Result<()> {
+    let explanations = c2p::explain_directory(path, &filter_options(filter)?)?;
+    for explanation in &explanations {
+        let mark = if explanation.included {
+            "+".bold().green()
+        } else {
+            "-".bold().red()
+        };
+        let suffix = if explanation.is_dir { "/" } else { "" };
+        println!(
+            "{}{}{} {}{} {}",
+            "[".bold().white(),
+            mark,
+            "]".bold().white(),
+            explanation.path.display(),
+            suffix,
+            format!("({})", explanation.reason).dimmed()
+        );
+    }
+
+    let included = explanations.iter().filter(|e| e.included).count();
+    println!(
+        "{}{}{} 包含 {} 個文件，排除 {} 項",
+        "[".bold().white(),
+        "i".bold().blue(),
+        "]".bold().white(),
+        included,
+        explanations.len() - included
+    );
+    Ok(())
+}
+
 fn get_git_diff_branch(args: &Args, template_content: &str) -> Result<String> {
     if template_contains_variables(template_content, &["git_diff_branch"]) {
         log::info!("生成兩個分支之間的 git diff...");
@@ -397,6 +828,7 @@ fn print_json_output(
     path: &Path,
     token_count: usize,
     model_info: &str,
+    costs: &[c2p::model::CostEstimate],
     paths: &[String],
 ) -> Result<String> {
     let json_output = json!({
@@ -404,13 +836,14 @@ fn print_json_output(
         "directory_name": c2p::path::label(path),
         "token_count": token_count,
         "model_info": model_info,
+        "costs": costs,
         "files": paths,
     });
     let json_string = serde_json::to_string_pretty(&json_output)?;
     Ok(json_string)
 }
 
-fn print_normal_output(token_count: usize, model_info: &str) {
+fn print_normal_output(token_count: usize, model_info: &str, costs: &[c2p::model::CostEstimate]) {
     println!(
         "{}{}{} Token 數量: {}, 模型資訊: {}",
         "[".bold().white(),
@@ -419,6 +852,220 @@ fn print_normal_output(token_count: usize, model_info: &str) {
         token_count.to_string().bold().yellow(),
         model_info
     );
+
+    for estimate in costs {
+        let cost = match (estimate.cost, estimate.input_price) {
+            (Some(cost), Some(price)) => format!(
+                "{} ({} tokens × ${:.2} / 1M tokens)",
+                format!("${:.4}", cost).bold().yellow(),
+                estimate.tokens,
+                price
+            ),
+            _ => "價格表中沒有此模型的價格".to_string(),
+        };
+        println!(
+            "{}{}{} 預估輸入成本 {}: {}",
+            "[".bold().white(),
+            "$".bold().blue(),
+            "]".bold().white(),
+            estimate.model,
+            cost
+        );
+    }
+}
+
+fn estimate_costs(
+    registry: &c2p::model::ModelRegistry,
+    model: Option<&c2p::model::ModelInfo>,
+    token_count: usize,
+    rendered: &str,
+    compare_models: &[String],
+) -> Result<Vec<c2p::model::CostEstimate>> {
+    let mut costs = Vec::new();
+    if let Some(model) = model {
+        costs.push(registry.estimate_cost(&model.name, token_count));
+    }
+
+    for name in compare_models {
+        if model.is_some_and(|model| &model.name == name) {
+            continue;
+        }
+        let compared = registry.get(name)?;
+        let tokenizer =
+            c2p::token::load_tokenizer(&Some(compared.encoding.clone()), &compared.tokenizer_file)?;
+        costs.push(registry.estimate_cost(name, tokenizer.count(rendered)));
+    }
+    Ok(costs)
+}
+
+fn check_context_window(
+    model: &c2p::model::ModelInfo,
+    token_count: usize,
+    fail_on_overflow: bool,
+) -> Result<()> {
+    if token_count <= model.context_window {
+        return Ok(());
+    }
+
+    let message = format!(
+        "提示共 {} tokens，超出 {} 的上下文窗口 ({} tokens)",
+        token_count, model.name, model.context_window
+    );
+    if fail_on_overflow {
+        return Err(anyhow::anyhow!(message));
+    }
+    println!(
+        "{}{}{} {}",
+        "[".bold().white(),
+        "!".bold().yellow(),
+        "]".bold().white(),
+        message.yellow()
+    );
+    Ok(())
+}
+
+fn print_budget_report(max_tokens: usize, cuts: &[c2p::budget::Cut]) {
+    if cuts.is_empty() {
+        return;
+    }
+
+    println!(
+        "{}{}{} 提示超出 {} tokens 上限，已調整以下文件:",
+        "[".bold().white(),
+        "!".bold().yellow(),
+        "]".bold().white(),
+        max_tokens.to_string().bold().yellow()
+    );
+    for cut in cuts {
+        let action = match cut.action {
+            c2p::budget::CutAction::Truncated => "截斷",
+            c2p::budget::CutAction::Elided => "省略",
+            c2p::budget::CutAction::Dropped => "移除",
+        };
+        println!(
+            "    - {} {} (節省 {} tokens)",
+            action.yellow(),
+            cut.path,
+            cut.tokens_saved
+        );
+    }
+}
+
+fn output_parts(parts: &[String], split_tokens: usize, args: &Args) -> Result<()> {
+    println!(
+        "{}{}{} 提示已分為 {} 部分，每部分最多 {} tokens",
+        "[".bold().white(),
+        "i".bold().blue(),
+        "]".bold().white(),
+        parts.len().to_string().bold().yellow(),
+        split_tokens
+    );
+
+    if let Some(output_path) = &args.output {
+        for (index, part) in parts.iter().enumerate() {
+            write_to_file(&c2p::split::part_file_name(output_path, index + 1), part)?;
+        }
+    } else if args.no_clipboard {
+        println!(
+            "{}{}{} {}",
+            "[".bold().white(),
+            "!".bold().yellow(),
+            "]".bold().white(),
+            "已禁用剪貼板且未指定輸出文件，分割後的提示未輸出到任何地方".yellow()
+        );
+    }
+
+    if !args.no_clipboard {
+        for (index, part) in parts.iter().enumerate() {
+            if index > 0 {
+                let prompt = format!("複製第 {}/{} 部分到剪貼板?", index + 1, parts.len());
+                if !Confirm::new(&prompt).with_default(true).prompt()? {
+                    break;
+                }
+            }
+            copy_to_clipboard_with_feedback(part);
+        }
+    }
+
+    Ok(())
+}
+
+#[allow(clippy::too_many_arguments)]
+fn output_map_reduce<F>(
+    output_dir: &Path,
+    path: &Path,
+    tree: &c2p::tree::SourceTree,
+    files: &[serde_json::Value],
+    args: &Args,
+    model: Option<&c2p::model::ModelInfo>,
+    tokenizer: &dyn c2p::token::Tokenizer,
+    mut render_prompt: F,
+) -> Result<(String, usize)>
+where
+    F: FnMut(&[serde_json::Value], &str) -> Result<String>,
+{
+    fs::create_dir_all(output_dir)
+        .with_context(|| format!("無法創建輸出目錄: {:?}", output_dir))?;
+
+    let mut manifest = Vec::new();
+    let mut all_rendered = String::new();
+    for (index, group) in c2p::group::group_files(files, args.group_depth)
+        .into_iter()
+        .enumerate()
+    {
+        let source_tree = group.source_tree(tree);
+        let mut group_files = group.files;
+        let (rendered, token_count) = if let Some(max_tokens) = args.max_tokens {
+            let (rendered, token_count, cuts) =
+                c2p::budget::fit_to_budget(&mut group_files, max_tokens, tokenizer, |files| {
+                    render_prompt(files, &source_tree)
+                })?;
+            print_budget_report(max_tokens, &cuts);
+            (rendered, token_count)
+        } else {
+            let rendered = render_prompt(&group_files, &source_tree)?;
+            let token_count = tokenizer.count(&rendered);
+            (rendered, token_count)
+        };
+
+        if let Some(model) = model {
+            check_context_window(model, token_count, args.fail_on_overflow)?;
+        }
+
+        let file_name = c2p::group::group_file_name(&group.name, index + 1);
+        write_to_file(&output_dir.join(&file_name).to_string_lossy(), &rendered)?;
+        all_rendered.push_str(&rendered);
+        manifest.push(c2p::group::ManifestEntry {
+            file: file_name,
+            group: group.name,
+            files: c2p::path::unique_paths(&group_files).len(),
+            tokens: token_count,
+        });
+    }
+
+    let index = c2p::group::render_index(&c2p::path::label(path), &tree.render(), &manifest)?;
+    let index_tokens = tokenizer.count(&index);
+    if let Some(model) = model {
+        check_context_window(model, index_tokens, args.fail_on_overflow)?;
+    }
+    write_to_file(
+        &output_dir
+            .join(c2p::group::INDEX_FILE_NAME)
+            .to_string_lossy(),
+        &index,
+    )?;
+
+    println!(
+        "{}{}{} 已生成 {} 個部分提示和 1 個索引提示: {}",
+        "[".bold().white(),
+        "i".bold().blue(),
+        "]".bold().white(),
+        manifest.len().to_string().bold().yellow(),
+        output_dir.display()
+    );
+    let token_count = manifest.iter().map(|entry| entry.tokens).sum::<usize>() + index_tokens;
+    all_rendered.push_str(&index);
+    Ok((all_rendered, token_count))
 }
 
 fn copy_to_clipboard_with_feedback(rendered: &str) {
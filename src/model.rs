@@ -0,0 +1,250 @@
+//! This module contains the model registry, which maps model names to their encoding and context window.
+
+use anyhow::{anyhow, Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// The built-in models as (name, encoding, context window, approximate).
+///
+/// Models without a public tiktoken encoding use the closest one as an approximation; set
+/// `tokenizer_file` in the models file for exact counts.
+const BUILTIN_MODELS: &[(&str, &str, usize, bool)] = &[
+    ("gpt-4o", "o200k", 128_000, false),
+    ("gpt-4o-mini", "o200k", 128_000, false),
+    ("gpt-4.1", "o200k", 1_047_576, false),
+    ("o1", "o200k", 200_000, false),
+    ("o3-mini", "o200k", 200_000, false),
+    ("gpt-4-turbo", "cl100k", 128_000, false),
+    ("gpt-4", "cl100k", 8_192, false),
+    ("gpt-3.5-turbo", "cl100k", 16_385, false),
+    ("text-embedding-ada-002", "cl100k", 8_191, false),
+    ("text-davinci-003", "p50k", 4_097, false),
+    ("code-davinci-002", "p50k", 8_001, false),
+    ("text-davinci-edit-001", "p50k_edit", 2_049, false),
+    ("code-davinci-edit-001", "p50k_edit", 2_049, false),
+    ("davinci", "r50k", 2_049, false),
+    ("claude-sonnet", "o200k", 200_000, true),
+    ("claude-opus", "o200k", 200_000, true),
+    ("claude-haiku", "o200k", 200_000, true),
+    ("gemini-1.5-pro", "o200k", 2_097_152, true),
+    ("llama-3-70b", "o200k", 8_192, true),
+    ("llama-3.1-70b", "o200k", 131_072, true),
+    ("qwen2.5-coder-32b", "o200k", 32_768, true),
+    ("mistral-large", "o200k", 131_072, true),
+];
+
+/// The built-in input prices in USD per million tokens.
+const BUILTIN_PRICES: &[(&str, f64)] = &[
+    ("gpt-4o", 2.5),
+    ("gpt-4o-mini", 0.15),
+    ("gpt-4.1", 2.0),
+    ("o1", 15.0),
+    ("o3-mini", 1.1),
+    ("gpt-4-turbo", 10.0),
+    ("gpt-4", 30.0),
+    ("gpt-3.5-turbo", 0.5),
+    ("text-embedding-ada-002", 0.1),
+    ("claude-sonnet", 3.0),
+    ("claude-opus", 15.0),
+    ("claude-haiku", 0.8),
+    ("gemini-1.5-pro", 1.25),
+];
+
+/// A model known to the registry.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct ModelInfo {
+    /// The name used to select the model with `--model`.
+    pub name: String,
+    /// The tiktoken encoding used to count tokens for the model.
+    pub encoding: String,
+    /// The maximum number of tokens the model accepts.
+    pub context_window: usize,
+    /// Whether the encoding only approximates the model's own tokenizer.
+    #[serde(default)]
+    pub approximate: bool,
+    /// An optional HuggingFace `tokenizer.json` file giving exact token counts.
+    ///
+    /// A relative path in a models file is resolved against the directory of the models file.
+    #[serde(default)]
+    pub tokenizer_file: Option<PathBuf>,
+}
+
+/// The estimated input cost of a prompt for one model.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct CostEstimate {
+    /// The name of the model.
+    pub model: String,
+    /// The token count of the prompt for the model.
+    pub tokens: usize,
+    /// The input price in USD per million tokens, if the pricing table has one.
+    pub input_price: Option<f64>,
+    /// The estimated cost in USD, if the pricing table has a price for the model.
+    pub cost: Option<f64>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ModelsFile {
+    #[serde(default)]
+    model: Vec<ModelInfo>,
+    #[serde(default)]
+    prices: HashMap<String, f64>,
+}
+
+/// The registry of known models and their input prices.
+#[derive(Debug, Clone)]
+pub struct ModelRegistry {
+    models: Vec<ModelInfo>,
+    prices: HashMap<String, f64>,
+}
+
+impl ModelRegistry {
+    /// Returns the registry containing only the built-in models.
+    pub fn builtin() -> Self {
+        let models = BUILTIN_MODELS
+            .iter()
+            .map(|(name, encoding, context_window, approximate)| ModelInfo {
+                name: name.to_string(),
+                encoding: encoding.to_string(),
+                context_window: *context_window,
+                approximate: *approximate,
+                tokenizer_file: None,
+            })
+            .collect();
+        let prices = BUILTIN_PRICES
+            .iter()
+            .map(|(name, price)| (name.to_string(), *price))
+            .collect();
+        ModelRegistry { models, prices }
+    }
+
+    /// Returns the built-in registry extended with the models from a TOML file.
+    ///
+    /// When `models_file` is `None`, `~/.c2p/models.toml` is used if it exists. Relative
+    /// `tokenizer_file` paths are resolved against the directory of the models file.
+    ///
+    /// # Arguments
+    ///
+    /// * `models_file` - An optional path to the TOML file with additional models.
+    ///
+    /// # Returns
+    ///
+    /// * `Result<ModelRegistry>` - The registry, or an error if the file cannot be read or parsed.
+    pub fn load(models_file: Option<&Path>) -> Result<Self> {
+        let mut registry = Self::builtin();
+        let models_file = match models_file {
+            Some(path) => Some(path.to_path_buf()),
+            None => default_models_file().filter(|path| path.is_file()),
+        };
+
+        if let Some(path) = models_file {
+            let content = fs::read_to_string(&path)
+                .with_context(|| format!("無法讀取模型文件: {:?}", path))?;
+            let mut file: ModelsFile = toml::from_str(&content)
+                .with_context(|| format!("無法解析模型文件: {:?}", path))?;
+            let directory = path.parent().unwrap_or(Path::new(""));
+            for model in &mut file.model {
+                if let Some(tokenizer_file) = &mut model.tokenizer_file {
+                    if tokenizer_file.is_relative() {
+                        *tokenizer_file = directory.join(&*tokenizer_file);
+                    }
+                }
+            }
+            registry.extend(file);
+        }
+        Ok(registry)
+    }
+
+    /// Adds the `[[model]]` entries and the `[prices]` table of a TOML document.
+    ///
+    /// Entries replace the built-in models and prices with the same name.
+    ///
+    /// # Arguments
+    ///
+    /// * `content` - The TOML document.
+    ///
+    /// # Returns
+    ///
+    /// * `Result<()>` - An error if the document is not a valid models file.
+    pub fn extend_from_toml(&mut self, content: &str) -> Result<()> {
+        let file: ModelsFile = toml::from_str(content)?;
+        self.extend(file);
+        Ok(())
+    }
+
+    /// Adds the models and prices of a parsed models file.
+    fn extend(&mut self, file: ModelsFile) {
+        for model in file.model {
+            match self
+                .models
+                .iter_mut()
+                .find(|known| known.name == model.name)
+            {
+                Some(known) => *known = model,
+                None => self.models.push(model),
+            }
+        }
+        self.prices.extend(file.prices);
+    }
+
+    /// Returns the model with the given name.
+    pub fn get(&self, name: &str) -> Result<&ModelInfo> {
+        self.models
+            .iter()
+            .find(|model| model.name == name)
+            .ok_or_else(|| {
+                let known: Vec<&str> = self
+                    .models
+                    .iter()
+                    .map(|model| model.name.as_str())
+                    .collect();
+                anyhow!("未知的模型 '{}'，可用的模型: {}", name, known.join(", "))
+            })
+    }
+
+    /// Returns the models that use the given encoding.
+    pub fn models_for_encoding(&self, encoding: &str) -> Vec<&ModelInfo> {
+        self.models
+            .iter()
+            .filter(|model| model.encoding == encoding)
+            .collect()
+    }
+
+    /// Returns the input price of the model in USD per million tokens.
+    pub fn input_price(&self, name: &str) -> Option<f64> {
+        self.prices.get(name).copied()
+    }
+
+    /// Estimates the input cost of a prompt with `tokens` tokens for the model.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The name of the model.
+    /// * `tokens` - The token count of the prompt for the model.
+    ///
+    /// # Returns
+    ///
+    /// * `CostEstimate` - The estimate, without a cost if the model has no price.
+    pub fn estimate_cost(&self, name: &str, tokens: usize) -> CostEstimate {
+        let input_price = self.input_price(name);
+        CostEstimate {
+            model: name.to_string(),
+            tokens,
+            input_price,
+            cost: input_price.map(|price| price * tokens as f64 / 1_000_000.0),
+        }
+    }
+
+    /// Returns all models in the registry.
+    pub fn models(&self) -> &[ModelInfo] {
+        &self.models
+    }
+}
+
+/// Returns the path of the default models file, `~/.c2p/models.toml`.
+pub fn default_models_file() -> Option<PathBuf> {
+    std::env::var_os("HOME")
+        .or_else(|| std::env::var_os("USERPROFILE"))
+        .map(|home| PathBuf::from(home).join(".c2p").join("models.toml"))
+}
@@ -1,101 +1,682 @@
 //! This module contains the functions for traversing the directory and processing the files.
 
-use crate::filter::should_include_file;
-use anyhow::Result;
-use ignore::WalkBuilder;
-use log::debug;
-use serde_json::json;
+use crate::chunk::{chunk_code, matching_regions, split_large_chunks, symbol_definitions, Chunk};
+use crate::filter::{is_generated, DefaultExcludes, FilterSet};
+use crate::git::get_last_commit_times;
+use crate::language::detect_language;
+use crate::sort::{compare, SortKey, SortOrder};
+use crate::token::Tokenizer;
+use crate::tree::{SourceTree, TreeNode, TreeOptions};
+use anyhow::{anyhow, Context, Result};
+use ignore::{DirEntry, WalkBuilder, WalkState};
+use log::{debug, warn};
+use rayon::prelude::*;
+use regex::Regex;
+use serde_json::{json, Value};
+use std::collections::{HashMap, HashSet};
 use std::fs;
-use std::path::Path;
-use termtree::Tree;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::SystemTime;
+
+/// The name of the project-level ignore files honoured like `.gitignore` in every directory.
+pub const IGNORE_FILE_NAME: &str = ".c2pignore";
+
+/// The marker appended in the source tree to the items left out by the default exclusion profile.
+pub const EXCLUDED_MARKER: &str = "[excluded by default]";
+
+/// Options controlling how `traverse_directory` walks the tree and renders the files.
+#[derive(Debug, Clone, Default)]
+pub struct TraverseOptions {
+    /// The patterns of files to include.
+    pub include: Vec<String>,
+    /// The patterns of files to exclude.
+    pub exclude: Vec<String>,
+    /// Whether to give priority to include patterns.
+    pub include_priority: bool,
+    /// Whether to add line numbers to the code.
+    pub line_number: bool,
+    /// Whether to exclude filtered files and folders from the source tree.
+    pub exclude_from_tree: bool,
+    /// Whether to not wrap the code inside markdown code blocks.
+    pub no_codeblock: bool,
+    /// Whether to match the patterns as unanchored wildcards against the absolute path instead of
+    /// as gitignore-style globs relative to the root.
+    pub legacy_patterns: bool,
+    /// The ordered `+pattern` and `-pattern` rules applied after the include and exclude
+    /// patterns, in which the last matching rule wins.
+    pub rules: Vec<String>,
+    /// The maximum number of tokens of a file before it is split into partial chunks.
+    pub max_file_tokens: Option<usize>,
+    /// The size in bytes above which a file is listed with a placeholder instead of its content.
+    pub max_file_size: Option<u64>,
+    /// The total size in bytes of the included files above which the traversal fails.
+    pub max_total_size: Option<u64>,
+    /// The only files, relative to the root, to include in the file list; the tree still shows
+    /// every file.
+    pub only_files: Option<HashSet<PathBuf>>,
+    /// The lowercase languages to include in the file list; all languages if empty.
+    pub only_languages: Vec<String>,
+    /// The lowercase languages to leave out of the file list.
+    pub skip_languages: Vec<String>,
+    /// A regular expression the content of a file must match for the file to be included.
+    pub grep: Option<Regex>,
+    /// The number of context lines around each match to keep, keeping only the matching regions
+    /// of a file instead of its whole content.
+    pub grep_context: Option<usize>,
+    /// The names of the functions, types, classes and impl blocks whose definitions replace the
+    /// whole content of the files; files defining none of them are left out.
+    pub symbols: Vec<String>,
+    /// Whether to include hidden files and directories.
+    pub hidden: bool,
+    /// Whether to disregard `.gitignore`, `.ignore` and `.c2pignore` files.
+    pub no_ignore: bool,
+    /// Whether to apply `.gitignore` files outside of git repositories.
+    pub ignore_without_git: bool,
+    /// Whether to follow symbolic links.
+    pub follow_symlinks: bool,
+    /// The maximum depth of the traversal below the root.
+    pub max_depth: Option<usize>,
+    /// Whether to disable the default exclusion profile for lockfiles, dependencies, build
+    /// output, minified assets, source maps and generated files.
+    pub no_default_excludes: bool,
+    /// The key the file list and the entries of each tree level are sorted by.
+    pub sort: SortKey,
+    /// The direction of the sort.
+    pub sort_order: SortOrder,
+    /// Whether to mix directories with files in the tree instead of listing them first.
+    pub no_dirs_first: bool,
+    /// The depth limit, collapsing and annotations of the source tree.
+    pub tree: TreeOptions,
+}
 
 /// Traverses the directory and returns the string representation of the tree and the vector of JSON file representations.
 ///
 /// # Arguments
 ///
 /// * `root_path` - The path to the root directory.
-/// * `include` - The patterns of files to include.
-/// * `exclude` - The patterns of files to exclude.
-/// * `include_priority` - Whether to give priority to include patterns.
-/// * `line_number` - Whether to add line numbers to the code.
+/// * `options` - The traversal and rendering options.
+/// * `tokenizer` - The tokenizer used to count the tokens of each file.
 ///
 /// # Returns
 ///
 /// A tuple containing the string representation of the directory tree and a vector of JSON representations of the files.
 pub fn traverse_directory(
     root_path: &Path,
-    include: &[String],
-    exclude: &[String],
-    include_priority: bool,
-    line_number: bool,
-    exclude_from_tree: bool,
-    no_codeblock: bool,
+    options: &TraverseOptions,
+    tokenizer: &dyn Tokenizer,
 ) -> Result<(String, Vec<serde_json::Value>)> {
+    let (tree, files) = traverse_directory_tree(root_path, options, tokenizer)?;
+    Ok((tree.render(), files))
+}
+
+/// Traverses the directory like `traverse_directory`, returning the source tree unrendered so
+/// that it can also be rendered for a subset of the files.
+///
+/// # Arguments
+///
+/// * `root_path` - The path to the root directory.
+/// * `options` - The traversal and rendering options.
+/// * `tokenizer` - The tokenizer used to count the tokens of each file.
+///
+/// # Returns
+///
+/// A tuple containing the source tree and a vector of JSON representations of the files.
+pub fn traverse_directory_tree(
+    root_path: &Path,
+    options: &TraverseOptions,
+    tokenizer: &dyn Tokenizer,
+) -> Result<(SourceTree, Vec<serde_json::Value>)> {
     // ~~~ Initialization ~~~
-    let mut files = Vec::new();
+    let TraverseOptions {
+        exclude_from_tree,
+        max_file_size,
+        max_total_size,
+        only_files,
+        only_languages,
+        skip_languages,
+        ..
+    } = options;
     let canonical_root_path = root_path.canonicalize()?;
+    let filter_set = build_filter_set(&canonical_root_path, options)?;
+    let default_excludes = build_default_excludes(&canonical_root_path, options);
     let parent_directory = label(&canonical_root_path);
 
+    // ~~~ Walk the directory ~~~
+    let entries = walk_directory(
+        &canonical_root_path,
+        options,
+        &filter_set,
+        default_excludes.as_ref(),
+    );
+
+    // ~~~ Select the files ~~~
+    let selections: Vec<Selection> = entries
+        .iter()
+        .map(|entry| {
+            let relative_path = entry
+                .path
+                .strip_prefix(&canonical_root_path)
+                .unwrap_or(&entry.path);
+            // A root that is a single file is matched by its file name
+            let filter_path = match entry.path.file_name() {
+                Some(file_name) if relative_path.as_os_str().is_empty() => Path::new(file_name),
+                _ => relative_path,
+            };
+            let included = filter_set.is_included(filter_path, entry.is_dir);
+            // The default exclusion profile only decides paths no user pattern includes
+            let explicitly_included =
+                included && filter_set.is_explicitly_included(filter_path, entry.is_dir);
+            let default_excluded = !explicitly_included
+                && default_excludes.as_ref().is_some_and(|default_excludes| {
+                    default_excludes
+                        .matching_pattern(filter_path, entry.is_dir)
+                        .is_some()
+                });
+            let selected = only_files
+                .as_ref()
+                .is_none_or(|only_files| only_files.contains(relative_path))
+                && is_language_selected(
+                    detect_language(&entry.path),
+                    only_languages,
+                    skip_languages,
+                );
+
+            let content = if entry.is_file && included && selected && !default_excluded {
+                match max_file_size.filter(|limit| entry.size > *limit) {
+                    Some(limit) => Some(Content::Placeholder(limit)),
+                    None => Some(Content::Code),
+                }
+            } else {
+                debug!("Excluded file: {:?}", entry.path.display());
+                None
+            };
+
+            Selection {
+                relative_path: relative_path.to_path_buf(),
+                included,
+                default_excluded,
+                content,
+            }
+        })
+        .collect();
+
+    // ~~~ Load the files ~~~
+    let loaded: Vec<Option<Loaded>> = entries
+        .par_iter()
+        .zip(&selections)
+        .map(|(entry, selection)| {
+            selection
+                .content
+                .map(|content| load_file(entry, content, options))
+        })
+        .collect();
+
+    // Only the files whose content ends up in the file list count toward the total size, which
+    // is checked before any file is tokenized
+    let total_size: u64 = entries
+        .iter()
+        .zip(&loaded)
+        .filter(|(_, loaded)| matches!(loaded, Some(Loaded::Code { .. })))
+        .map(|(entry, _)| entry.size)
+        .sum();
+    if let Some(limit) = max_total_size.filter(|limit| total_size > *limit) {
+        return Err(anyhow!(
+            "包含文件的總大小 {} 超過上限 {}，請使用 --nor 排除大文件或使用 --max-file-size 跳過其內容",
+            format_size(total_size),
+            format_size(limit)
+        ));
+    }
+
+    // ~~~ Process the files ~~~
+    let outcomes: Vec<FileOutcome> = entries
+        .par_iter()
+        .zip(&selections)
+        .zip(loaded)
+        .map(|((entry, selection), loaded)| match loaded {
+            Some(loaded) => {
+                let file_path =
+                    format!("{}/{}", parent_directory, selection.relative_path.display());
+                process_file(entry, &file_path, loaded, options, tokenizer)
+            }
+            None => FileOutcome::Excluded,
+        })
+        .collect();
+
     // ~~~ Build the Tree ~~~
-    let tree = WalkBuilder::new(&canonical_root_path)
-        .git_ignore(true)
-        .build()
-        .filter_map(|e| e.ok())
-        .fold(Tree::new(parent_directory.to_owned()), |mut root, entry| {
-            let path = entry.path();
-            if let Ok(relative_path) = path.strip_prefix(&canonical_root_path) {
-                let mut current_tree = &mut root;
-                for component in relative_path.components() {
-                    let component_str = component.as_os_str().to_string_lossy().to_string();
-
-                    // Check if the current component should be excluded from the tree
-                    if exclude_from_tree && !should_include_file(path, include, exclude, include_priority) {
-                        break;
-                    }
-
-                    current_tree = if let Some(pos) = current_tree
-                        .leaves
-                        .iter_mut()
-                        .position(|child| child.root == component_str)
-                    {
-                        &mut current_tree.leaves[pos]
-                    } else {
-                        let new_tree = Tree::new(component_str.clone());
-                        current_tree.leaves.push(new_tree);
-                        current_tree.leaves.last_mut().unwrap()
+    let git_times = if options.sort == SortKey::GitRecency {
+        let paths = entries
+            .iter()
+            .zip(&selections)
+            .filter(|(entry, _)| entry.is_file)
+            .map(|(_, selection)| selection.relative_path.clone())
+            .collect();
+        get_last_commit_times(&canonical_root_path, &paths).unwrap_or_else(|e| {
+            warn!("無法讀取 git 歷史，文件將按路徑排序: {}", e);
+            HashMap::new()
+        })
+    } else {
+        HashMap::new()
+    };
+
+    let mut root = TreeNode::root(parent_directory.to_owned());
+    let mut files = Vec::new();
+    for ((entry, selection), outcome) in entries.iter().zip(selections).zip(outcomes) {
+        let Selection {
+            relative_path,
+            included,
+            default_excluded,
+            ..
+        } = selection;
+        // Check if the path should be excluded from the tree
+        if *exclude_from_tree && !included && !default_excluded {
+            continue;
+        }
+
+        let node = root.insert(&relative_path, entry.is_dir);
+        if !entry.is_dir {
+            node.fields.size = entry.size;
+            node.fields.mtime = entry.mtime;
+            node.fields.git_time = git_times.get(&relative_path).copied();
+        }
+
+        match outcome {
+            FileOutcome::Entries { entries, lines } => {
+                node.listed = true;
+                node.lines = lines;
+                node.fields.tokens = entries
+                    .iter()
+                    .filter_map(|entry| entry["tokens"].as_u64())
+                    .sum::<u64>() as usize;
+                files.push((node.fields.clone(), entries));
+            }
+            FileOutcome::Generated => node.excluded_by_default = true,
+            FileOutcome::Excluded if default_excluded => node.excluded_by_default = true,
+            FileOutcome::Excluded => {}
+        }
+    }
+
+    // ~~~ Sort the files ~~~
+    files.sort_by(|a, b| compare(&a.0, &b.0, options.sort, options.sort_order));
+    let files = files.into_iter().flat_map(|(_, entries)| entries).collect();
+    let tree = SourceTree::new(
+        root,
+        options.sort,
+        options.sort_order,
+        !options.no_dirs_first,
+        options.tree.clone(),
+    );
+
+    Ok((tree, files))
+}
+
+/// A path found by the walk.
+struct WalkedEntry {
+    path: PathBuf,
+    is_dir: bool,
+    is_file: bool,
+    size: u64,
+    mtime: Option<SystemTime>,
+}
+
+/// What the traversal decided for a walked path.
+struct Selection {
+    relative_path: PathBuf,
+    included: bool,
+    default_excluded: bool,
+    content: Option<Content>,
+}
+
+/// How the content of a selected file is rendered.
+#[derive(Clone, Copy)]
+enum Content {
+    /// A placeholder replaces the content of a file over the given size limit.
+    Placeholder(u64),
+    /// The code of the file is read and rendered.
+    Code,
+}
+
+/// A selected file after the checks that need no tokenizer.
+enum Loaded {
+    /// A placeholder replaces the content of a file over the given size limit.
+    Placeholder(u64),
+    /// The code of the file, with the regions kept by `--symbol` or `--grep-context` and their
+    /// label, if any.
+    Code {
+        code: String,
+        regions: Option<(Vec<Chunk>, &'static str)>,
+    },
+    /// The file is left out of the file list.
+    Skipped(FileOutcome),
+}
+
+/// The result of processing a walked path.
+enum FileOutcome {
+    /// The file list entries of the file, a single one unless the file is split into chunks, and
+    /// the number of lines of the file if its content was read.
+    Entries {
+        entries: Vec<serde_json::Value>,
+        lines: Option<usize>,
+    },
+    /// The file is generated code, left out by the default exclusion profile.
+    Generated,
+    /// The path is not part of the file list.
+    Excluded,
+}
+
+/// Walks the directory on all cores and returns its paths sorted, so that the output does not
+/// depend on the order in which the threads visit them.
+fn walk_directory(
+    canonical_root_path: &Path,
+    options: &TraverseOptions,
+    filter_set: &FilterSet,
+    default_excludes: Option<&DefaultExcludes>,
+) -> Vec<WalkedEntry> {
+    let entries = Mutex::new(Vec::new());
+    build_walker(canonical_root_path, options, filter_set, default_excludes)
+        .build_parallel()
+        .run(|| {
+            let entries = &entries;
+            Box::new(move |entry| {
+                if let Ok(entry) = entry {
+                    let is_dir = entry
+                        .file_type()
+                        .is_some_and(|file_type| file_type.is_dir());
+                    let path = entry.into_path();
+                    let metadata = fs::metadata(&path).ok();
+                    let walked = WalkedEntry {
+                        is_dir,
+                        is_file: metadata.as_ref().is_some_and(|metadata| metadata.is_file()),
+                        size: metadata.as_ref().map_or(0, |metadata| metadata.len()),
+                        mtime: metadata.and_then(|metadata| metadata.modified().ok()),
+                        path,
                     };
+                    entries.lock().unwrap().push(walked);
                 }
+                WalkState::Continue
+            })
+        });
 
-                // ~~~ Process the file ~~~
-                if path.is_file() && should_include_file(path, include, exclude, include_priority) {
-                    if let Ok(code_bytes) = fs::read(path) {
-                        let code = String::from_utf8_lossy(&code_bytes);
-
-                        let code_block = wrap_code_block(&code, path.extension().and_then(|ext| ext.to_str()).unwrap_or(""), line_number, no_codeblock);
-
-                        if !code.trim().is_empty() && !code.contains(char::REPLACEMENT_CHARACTER) {
-                            let file_path = format!("{}/{}", parent_directory, relative_path.display());
-                            files.push(json!({
-                                "path": file_path,
-                                "extension": path.extension().and_then(|ext| ext.to_str()).unwrap_or(""),
-                                "code": code_block,
-                            }));
-                            debug!(target: "included_files", "Included file: {}", file_path);
-                        } else {
-                            debug!("Excluded file (empty or invalid UTF-8): {}", path.display());
-                        }
-                    } else {
-                        debug!("Failed to read file: {}", path.display());
-                    }
-                } else {
-                    debug!("Excluded file: {:?}", path.display());
-                }
+    let mut entries = entries.into_inner().unwrap();
+    entries.sort_by(|a, b| a.path.cmp(&b.path));
+    entries
+}
+
+/// Reads a selected file and runs the checks that need no tokenizer on its content.
+fn load_file(entry: &WalkedEntry, content: Content, options: &TraverseOptions) -> Loaded {
+    let TraverseOptions {
+        grep,
+        grep_context,
+        symbols,
+        no_default_excludes,
+        ..
+    } = options;
+    let path = entry.path.as_path();
+
+    if let Content::Placeholder(limit) = content {
+        return Loaded::Placeholder(limit);
+    }
+    let Ok(code_bytes) = fs::read(path) else {
+        debug!("Failed to read file: {}", path.display());
+        return Loaded::Skipped(FileOutcome::Excluded);
+    };
+    let code = String::from_utf8_lossy(&code_bytes).into_owned();
+
+    if code.trim().is_empty() || code.contains(char::REPLACEMENT_CHARACTER) {
+        debug!("Excluded file (empty or invalid UTF-8): {}", path.display());
+        return Loaded::Skipped(FileOutcome::Excluded);
+    }
+    if !*no_default_excludes && is_generated(&code) {
+        debug!("Excluded generated file: {}", path.display());
+        return Loaded::Skipped(FileOutcome::Generated);
+    }
+    if grep.as_ref().is_some_and(|grep| !grep.is_match(&code)) {
+        debug!("Excluded file (no match for --grep): {}", path.display());
+        return Loaded::Skipped(FileOutcome::Excluded);
+    }
+
+    let extension = path.extension().and_then(|ext| ext.to_str()).unwrap_or("");
+    let regions = match (grep, grep_context) {
+        // Only the definitions of the symbols are kept, always with line numbers
+        _ if !symbols.is_empty() => Some((
+            symbol_definitions(&code, extension, symbols),
+            "symbol definition",
+        )),
+        // Only the regions around the matches are kept
+        (Some(grep), Some(context)) => {
+            Some((matching_regions(&code, grep, *context), "matching region"))
+        }
+        _ => None,
+    };
+    if regions
+        .as_ref()
+        .is_some_and(|(chunks, _)| chunks.is_empty())
+    {
+        debug!(
+            "Excluded file (no definition of --symbol): {}",
+            path.display()
+        );
+        return Loaded::Skipped(FileOutcome::Excluded);
+    }
+
+    Loaded::Code { code, regions }
+}
+
+/// Renders a loaded file into its file list entries.
+fn process_file(
+    entry: &WalkedEntry,
+    file_path: &str,
+    loaded: Loaded,
+    options: &TraverseOptions,
+    tokenizer: &dyn Tokenizer,
+) -> FileOutcome {
+    let TraverseOptions {
+        line_number,
+        no_codeblock,
+        max_file_tokens,
+        symbols,
+        ..
+    } = options;
+    let path = entry.path.as_path();
+    let extension = path.extension().and_then(|ext| ext.to_str()).unwrap_or("");
+    let language = detect_language(path);
+
+    let (code, regions) = match loaded {
+        Loaded::Placeholder(limit) => {
+            // Oversized files are listed with a placeholder instead of their content
+            let code_block = format!(
+                "[content skipped: the file size of {} exceeds the {} limit]",
+                format_size(entry.size),
+                format_size(limit)
+            );
+            debug!(target: "included_files", "Skipped content of large file: {}", file_path);
+            return FileOutcome::Entries {
+                entries: vec![json!({
+                    "path": file_path,
+                    "extension": extension,
+                    "language": language,
+                    "code": code_block,
+                    "tokens": tokenizer.count(&code_block),
+                    "size": entry.size,
+                    "skipped": true,
+                })],
+                lines: None,
+            };
+        }
+        Loaded::Skipped(outcome) => return outcome,
+        Loaded::Code { code, regions } => (code, regions),
+    };
+
+    let split = |chunks| match max_file_tokens {
+        Some(limit) => split_large_chunks(chunks, extension, *limit, tokenizer),
+        None => chunks,
+    };
+    let chunks = match regions {
+        // The kept regions are split like files when oversized
+        Some((chunks, kind)) => Some((split(chunks), kind)),
+        // Oversized files are split at syntactic boundaries into labelled partial entries
+        None => max_file_tokens
+            .filter(|limit| tokenizer.count(&code) > *limit)
+            .map(|limit| {
+                (
+                    chunk_code(&code, extension, limit, tokenizer),
+                    "partial file",
+                )
+            }),
+    };
+
+    match chunks {
+        Some((chunks, kind)) => {
+            let total_lines = code.lines().count();
+            let lines = chunks
+                .iter()
+                .map(|chunk| chunk.end_line - chunk.start_line + 1)
+                .sum();
+            let entries = chunks
+                .into_iter()
+                .map(|chunk| {
+                    let code_block = format!(
+                        "Lines {}-{} of {} ({}):\n{}",
+                        chunk.start_line,
+                        chunk.end_line,
+                        total_lines,
+                        kind,
+                        wrap_code_block(
+                            &chunk.code,
+                            extension,
+                            *line_number || !symbols.is_empty(),
+                            *no_codeblock,
+                            chunk.start_line
+                        )
+                    );
+                    json!({
+                        "path": file_path,
+                        "extension": extension,
+                        "language": language,
+                        "code": code_block,
+                        "tokens": tokenizer.count(&code_block),
+                        "partial": true,
+                        "start_line": chunk.start_line,
+                        "end_line": chunk.end_line,
+                        "total_lines": total_lines,
+                    })
+                })
+                .collect();
+            debug!(target: "included_files", "Included file in chunks: {}", file_path);
+            FileOutcome::Entries {
+                entries,
+                lines: Some(lines),
+            }
+        }
+        None => {
+            let code_block = wrap_code_block(&code, extension, *line_number, *no_codeblock, 1);
+            debug!(target: "included_files", "Included file: {}", file_path);
+            FileOutcome::Entries {
+                entries: vec![json!({
+                    "path": file_path,
+                    "extension": extension,
+                    "language": language,
+                    "code": code_block,
+                    "tokens": tokenizer.count(&code_block),
+                })],
+                lines: Some(code.lines().count()),
             }
+        }
+    }
+}
+
+/// Compiles the include and exclude filters of the options for the canonical traversal root.
+pub(crate) fn build_filter_set(
+    canonical_root_path: &Path,
+    options: &TraverseOptions,
+) -> Result<FilterSet> {
+    if options.legacy_patterns {
+        Ok(FilterSet::legacy(
+            canonical_root_path,
+            &options.include,
+            &options.exclude,
+            options.include_priority,
+        ))
+    } else {
+        FilterSet::new(
+            canonical_root_path,
+            &options.include,
+            &options.exclude,
+            options.include_priority,
+            &options.rules,
+        )
+    }
+}
+
+/// Compiles the default exclusion profile for the canonical traversal root, unless disabled.
+pub(crate) fn build_default_excludes(
+    canonical_root_path: &Path,
+    options: &TraverseOptions,
+) -> Option<DefaultExcludes> {
+    (!options.no_default_excludes).then(|| DefaultExcludes::new(canonical_root_path))
+}
 
-            root
+/// Returns a walker over the canonical traversal root honouring the ignore, hidden, symlink and
+/// depth options.
+///
+/// The walker does not descend into directories left out by the default exclusion profile,
+/// except into the paths the filters explicitly include.
+pub(crate) fn build_walker(
+    canonical_root_path: &Path,
+    options: &TraverseOptions,
+    filter_set: &FilterSet,
+    default_excludes: Option<&DefaultExcludes>,
+) -> WalkBuilder {
+    let root = canonical_root_path.to_path_buf();
+    let filter_set = filter_set.clone();
+    let default_excludes = default_excludes.cloned();
+    let mut walker = WalkBuilder::new(canonical_root_path);
+    walker
+        .hidden(!options.hidden)
+        .git_ignore(!options.no_ignore)
+        .git_global(!options.no_ignore)
+        .git_exclude(!options.no_ignore)
+        .ignore(!options.no_ignore)
+        .parents(!options.no_ignore)
+        .require_git(!options.ignore_without_git)
+        .follow_links(options.follow_symlinks)
+        .max_depth(options.max_depth)
+        // The git directory is never part of the prompt, even with hidden files included
+        .filter_entry(move |entry| {
+            entry.file_name() != ".git"
+                && !is_in_excluded_directory(&root, &filter_set, default_excludes.as_ref(), entry)
         });
+    if !options.no_ignore {
+        walker.add_custom_ignore_filename(IGNORE_FILE_NAME);
+    }
+    walker
+}
 
-    Ok((tree.to_string(), files))
+/// Determines whether a walked entry lies directly inside a directory left out by the default
+/// exclusion profile and is not explicitly included by the filters.
+pub(crate) fn is_in_excluded_directory(
+    canonical_root_path: &Path,
+    filter_set: &FilterSet,
+    default_excludes: Option<&DefaultExcludes>,
+    entry: &DirEntry,
+) -> bool {
+    let Some(default_excludes) = default_excludes else {
+        return false;
+    };
+    let path = entry.path();
+    let in_excluded_directory = path
+        .parent()
+        .and_then(|parent| parent.strip_prefix(canonical_root_path).ok())
+        .is_some_and(|parent| default_excludes.matching_pattern(parent, true).is_some());
+    in_excluded_directory
+        && !path
+            .strip_prefix(canonical_root_path)
+            .is_ok_and(|relative_path| {
+                let is_dir = entry
+                    .file_type()
+                    .is_some_and(|file_type| file_type.is_dir());
+                filter_set.is_explicitly_included(relative_path, is_dir)
+            })
 }
 
 /// Returns the file name or the string representation of the path.
@@ -124,6 +705,88 @@ pub fn label<P: AsRef<Path>>(p: P) -> String {
     }
 }
 
+/// Returns the distinct paths of the file representations, in their original order.
+///
+/// Files split by `--max-file-tokens` are represented by several chunks sharing the same path.
+///
+/// # Arguments
+///
+/// * `files` - The JSON file representations returned by `traverse_directory`.
+///
+/// # Returns
+///
+/// * `Vec<String>` - The path of each file, listed once.
+pub fn unique_paths(files: &[Value]) -> Vec<String> {
+    let mut seen = HashSet::new();
+    files
+        .iter()
+        .filter_map(|file| file["path"].as_str())
+        .filter(|path| seen.insert(*path))
+        .map(String::from)
+        .collect()
+}
+
+/// Determines whether a file of the given language passes the language filters.
+fn is_language_selected(
+    language: Option<&str>,
+    only_languages: &[String],
+    skip_languages: &[String],
+) -> bool {
+    let listed = |languages: &[String]| language.is_some_and(|l| languages.iter().any(|x| x == l));
+    (only_languages.is_empty() || listed(only_languages)) && !listed(skip_languages)
+}
+
+/// Parses a size such as `1024`, `10k`, `1.5MB` or `2GiB` into bytes, using binary units.
+///
+/// # Arguments
+///
+/// * `size` - The size, optionally followed by a `k`, `m` or `g` unit with an optional `b` or `ib`.
+///
+/// # Returns
+///
+/// * `Result<u64>` - The size in bytes, or an error if the size cannot be parsed.
+pub fn parse_size(size: &str) -> Result<u64> {
+    let size = size.trim();
+    let split = size
+        .find(|c: char| !c.is_ascii_digit() && c != '.')
+        .unwrap_or(size.len());
+    let (number, unit) = size.split_at(split);
+    let multiplier: u64 = match unit.trim().to_ascii_lowercase().as_str() {
+        "" | "b" => 1,
+        "k" | "kb" | "kib" => 1 << 10,
+        "m" | "mb" | "mib" => 1 << 20,
+        "g" | "gb" | "gib" => 1 << 30,
+        _ => return Err(anyhow!("無效的大小單位 '{}'，可用的單位: k, m, g", unit)),
+    };
+    let number: f64 = number
+        .parse()
+        .with_context(|| format!("無效的大小 '{}'", size))?;
+    Ok((number * multiplier as f64) as u64)
+}
+
+/// Formats a size in bytes with a binary unit, e.g. `1.5 MB`.
+///
+/// # Arguments
+///
+/// * `bytes` - The size in bytes.
+///
+/// # Returns
+///
+/// * `String` - The formatted size.
+pub fn format_size(bytes: u64) -> String {
+    const UNITS: &[&str] = &["KB", "MB", "GB"];
+    if bytes < 1 << 10 {
+        return format!("{} B", bytes);
+    }
+    let mut size = bytes as f64 / 1024.0;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    format!("{:.1} {}", size, UNITS[unit])
+}
+
 /// Wraps the code block with a delimiter and adds line numbers if required.
 ///
 /// # Arguments
@@ -132,17 +795,28 @@ pub fn label<P: AsRef<Path>>(p: P) -> String {
 /// * `extension` - The file extension of the code block.
 /// * `line_numbers` - Whether to add line numbers to the code.
 /// * `no_codeblock` - Whether to not wrap the code block with a delimiter.
+/// * `first_line` - The line number of the first line of the code block.
 ///
 /// # Returns
 ///
 /// * `String` - The wrapped code block.
-fn wrap_code_block(code: &str, extension: &str, line_numbers: bool, no_codeblock: bool) -> String {
+fn wrap_code_block(
+    code: &str,
+    extension: &str,
+    line_numbers: bool,
+    no_codeblock: bool,
+    first_line: usize,
+) -> String {
     let delimiter = "`".repeat(3);
     let mut code_with_line_numbers = String::new();
 
     if line_numbers {
         for (line_number, line) in code.lines().enumerate() {
-            code_with_line_numbers.push_str(&format!("{:4} | {}\n", line_number + 1, line));
+            code_with_line_numbers.push_str(&format!(
+                "{:4} | {}\n",
+                line_number + first_line,
+                line
+            ));
         }
     } else {
         code_with_line_numbers = code.to_string();
@@ -0,0 +1,141 @@
+//! This module contains the orderings of the file list and of the source tree.
+
+use anyhow::{anyhow, Result};
+use std::cmp::Ordering;
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::time::SystemTime;
+
+/// The key the files and the tree entries are sorted by.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SortKey {
+    /// The file or directory name.
+    Name,
+    /// The path relative to the root.
+    #[default]
+    Path,
+    /// The size in bytes; the total size of the contents for directories.
+    Size,
+    /// The modification time; the latest one of the contents for directories.
+    Mtime,
+    /// The number of tokens in the prompt; the total of the contents for directories.
+    Tokens,
+    /// The time of the last commit changing the file; the latest one of the contents for
+    /// directories. Files without a commit come last in ascending order.
+    GitRecency,
+}
+
+impl FromStr for SortKey {
+    type Err = anyhow::Error;
+
+    fn from_str(key: &str) -> Result<Self> {
+        match key.trim().to_ascii_lowercase().as_str() {
+            "name" => Ok(SortKey::Name),
+            "path" => Ok(SortKey::Path),
+            "size" => Ok(SortKey::Size),
+            "mtime" => Ok(SortKey::Mtime),
+            "tokens" => Ok(SortKey::Tokens),
+            "git-recency" => Ok(SortKey::GitRecency),
+            _ => Err(anyhow!(
+                "未知的排序方式 '{}'，可用的排序方式: name, path, size, mtime, tokens, git-recency",
+                key
+            )),
+        }
+    }
+}
+
+/// The direction of a sort.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SortOrder {
+    #[default]
+    Asc,
+    Desc,
+}
+
+impl FromStr for SortOrder {
+    type Err = anyhow::Error;
+
+    fn from_str(order: &str) -> Result<Self> {
+        match order.trim().to_ascii_lowercase().as_str() {
+            "asc" => Ok(SortOrder::Asc),
+            "desc" => Ok(SortOrder::Desc),
+            _ => Err(anyhow!("未知的排序方向 '{}'，可用的方向: asc, desc", order)),
+        }
+    }
+}
+
+/// The attributes of a file or directory that can be sorted by.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SortFields {
+    /// The path relative to the root.
+    pub path: PathBuf,
+    /// The size in bytes.
+    pub size: u64,
+    /// The modification time.
+    pub mtime: Option<SystemTime>,
+    /// The number of tokens in the prompt.
+    pub tokens: usize,
+    /// The time of the last commit in seconds since the Unix epoch.
+    pub git_time: Option<i64>,
+}
+
+impl SortFields {
+    /// Returns the fields of a directory from the fields of the entries inside it.
+    ///
+    /// Sizes and tokens are summed and the latest times are kept, an entry without a commit
+    /// making the whole directory the most recent one, as in `compare`.
+    pub fn aggregate<'a>(path: PathBuf, entries: impl IntoIterator<Item = &'a SortFields>) -> Self {
+        let mut fields = SortFields {
+            path,
+            ..SortFields::default()
+        };
+        let mut uncommitted = false;
+        for entry in entries {
+            fields.size += entry.size;
+            fields.tokens += entry.tokens;
+            fields.mtime = fields.mtime.max(entry.mtime);
+            match entry.git_time {
+                Some(time) => fields.git_time = fields.git_time.max(Some(time)),
+                None => uncommitted = true,
+            }
+        }
+        if uncommitted {
+            fields.git_time = None;
+        }
+        fields
+    }
+}
+
+/// Compares two entries by a key in a direction, breaking ties by ascending path so that the order
+/// is total.
+///
+/// # Arguments
+///
+/// * `a` - The fields of the first entry.
+/// * `b` - The fields of the second entry.
+/// * `key` - The key to sort by.
+/// * `order` - The direction of the sort.
+///
+/// # Returns
+///
+/// * `Ordering` - The ordering of the two entries.
+pub fn compare(a: &SortFields, b: &SortFields, key: SortKey, order: SortOrder) -> Ordering {
+    let ordering = match key {
+        SortKey::Name => a.path.file_name().cmp(&b.path.file_name()),
+        SortKey::Path => a.path.cmp(&b.path),
+        SortKey::Size => a.size.cmp(&b.size),
+        SortKey::Mtime => a.mtime.cmp(&b.mtime),
+        SortKey::Tokens => a.tokens.cmp(&b.tokens),
+        // Files without a commit are the most recent ones
+        SortKey::GitRecency => match (a.git_time, b.git_time) {
+            (Some(a), Some(b)) => a.cmp(&b),
+            (a, b) => b.is_some().cmp(&a.is_some()),
+        },
+    };
+
+    match order {
+        SortOrder::Asc => ordering,
+        SortOrder::Desc => ordering.reverse(),
+    }
+    .then_with(|| a.path.cmp(&b.path))
+}
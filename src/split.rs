@@ -0,0 +1,204 @@
+//! This module splits an oversized rendered prompt into numbered parts.
+//!
+//! Parts break on file boundaries where possible. A file that does not fit into a part on its
+//! own is split between lines, and a single line that does not fit is split between characters.
+
+use crate::token::Tokenizer;
+use anyhow::{anyhow, Result};
+use serde_json::Value;
+use std::path::Path;
+
+/// Splits the rendered prompt into parts of at most `max_tokens` tokens each.
+///
+/// Every part starts with a "part k of n" header and ends with an instruction telling the model
+/// to wait for the remaining parts, or to respond once the last part has been sent.
+///
+/// # Arguments
+///
+/// * `rendered` - The rendered prompt.
+/// * `files` - The JSON file representations used to find the file boundaries in the prompt.
+/// * `max_tokens` - The maximum number of tokens of each part, including its header and footer.
+/// * `tokenizer` - The tokenizer used to count tokens.
+///
+/// # Returns
+///
+/// * `Result<Vec<String>>` - The parts, or an error if `max_tokens` cannot fit a header and footer.
+pub fn split_prompt(
+    rendered: &str,
+    files: &[Value],
+    max_tokens: usize,
+    tokenizer: &dyn Tokenizer,
+) -> Result<Vec<String>> {
+    // Leave a small margin, as token counts of joined pieces can differ slightly from their sum.
+    let overhead =
+        tokenizer.count(&part_header(999, 999)) + tokenizer.count(&part_footer(998, 999)) + 2;
+    if max_tokens <= overhead {
+        return Err(anyhow!(
+            "每部分的 token 上限 {} 太小，至少需要 {} tokens 容納分段說明",
+            max_tokens,
+            overhead + 1
+        ));
+    }
+    let available = max_tokens - overhead;
+
+    let mut chunks = Vec::new();
+    let mut current = String::new();
+    let mut current_tokens = 0;
+    for segment in split_at_file_boundaries(rendered, files) {
+        for piece in split_to_fit(segment, available, tokenizer) {
+            let tokens = tokenizer.count(piece);
+            if current_tokens + tokens > available && !current.is_empty() {
+                chunks.push(std::mem::take(&mut current));
+                current_tokens = 0;
+            }
+            current.push_str(piece);
+            current_tokens += tokens;
+        }
+    }
+    if !current.is_empty() {
+        chunks.push(current);
+    }
+
+    let total = chunks.len();
+    Ok(chunks
+        .iter()
+        .enumerate()
+        .map(|(index, chunk)| {
+            format!(
+                "{}{}\n{}",
+                part_header(index + 1, total),
+                chunk.trim_end(),
+                part_footer(index + 1, total)
+            )
+        })
+        .collect())
+}
+
+/// Returns the file name of a part, numbering the file stem, e.g. `prompt.md` becomes `prompt.001.md`.
+///
+/// # Arguments
+///
+/// * `output_path` - The output file path given by the user.
+/// * `part` - The 1-based number of the part.
+///
+/// # Returns
+///
+/// * `String` - The file name of the part.
+pub fn part_file_name(output_path: &str, part: usize) -> String {
+    let path = Path::new(output_path);
+    let stem = path
+        .file_stem()
+        .and_then(|s| s.to_str())
+        .unwrap_or("prompt");
+    let file_name = match path.extension().and_then(|ext| ext.to_str()) {
+        Some(extension) => format!("{}.{:03}.{}", stem, part, extension),
+        None => format!("{}.{:03}", stem, part),
+    };
+    path.with_file_name(file_name)
+        .to_string_lossy()
+        .into_owned()
+}
+
+/// Splits the prompt into segments starting at the line that mentions each file path.
+fn split_at_file_boundaries<'a>(rendered: &'a str, files: &[Value]) -> Vec<&'a str> {
+    let mut boundaries = vec![0];
+    let mut cursor = 0;
+    for path in files.iter().filter_map(|file| file["path"].as_str()) {
+        if let Some(position) = rendered[cursor..].find(path) {
+            let position = cursor + position;
+            let line_start = rendered[..position].rfind('\n').map_or(0, |i| i + 1);
+            if line_start > *boundaries.last().unwrap() {
+                boundaries.push(line_start);
+            }
+            cursor = position + path.len();
+        }
+    }
+    boundaries.push(rendered.len());
+
+    boundaries
+        .windows(2)
+        .map(|window| &rendered[window[0]..window[1]])
+        .collect()
+}
+
+/// Splits a segment between lines so that each piece fits into `available` tokens.
+///
+/// A single line that does not fit is split between characters.
+fn split_to_fit<'a>(segment: &'a str, available: usize, tokenizer: &dyn Tokenizer) -> Vec<&'a str> {
+    if tokenizer.count(segment) <= available {
+        return vec![segment];
+    }
+
+    let mut pieces = Vec::new();
+    let mut start = 0;
+    let mut end = 0;
+    let mut tokens = 0;
+    for line in segment.split_inclusive('\n') {
+        let line_tokens = tokenizer.count(line);
+        if tokens + line_tokens > available && end > start {
+            pieces.push(&segment[start..end]);
+            start = end;
+            tokens = 0;
+        }
+        if line_tokens > available {
+            pieces.extend(split_line(line, available, tokenizer));
+            start = end + line.len();
+        } else {
+            tokens += line_tokens;
+        }
+        end += line.len();
+    }
+    if start < segment.len() {
+        pieces.push(&segment[start..]);
+    }
+    pieces
+}
+
+/// Splits a line into the longest runs of characters that fit into `available` tokens.
+fn split_line<'a>(line: &'a str, available: usize, tokenizer: &dyn Tokenizer) -> Vec<&'a str> {
+    let mut pieces = Vec::new();
+    let mut rest = line;
+    while !rest.is_empty() {
+        let boundaries: Vec<usize> = rest
+            .char_indices()
+            .map(|(i, _)| i)
+            .skip(1)
+            .chain(std::iter::once(rest.len()))
+            .collect();
+        // Binary search for the longest prefix that fits, keeping at least one character.
+        let (mut low, mut high) = (0, boundaries.len() - 1);
+        while low < high {
+            let middle = (low + high).div_ceil(2);
+            if tokenizer.count(&rest[..boundaries[middle]]) <= available {
+                low = middle;
+            } else {
+                high = middle - 1;
+            }
+        }
+        let (piece, remainder) = rest.split_at(boundaries[low]);
+        pieces.push(piece);
+        rest = remainder;
+    }
+    pieces
+}
+
+fn part_header(part: usize, total: usize) -> String {
+    format!(
+        "[Part {} of {}]\nThis prompt is split into {} parts. Read every part before responding.\n\n",
+        part, total, total
+    )
+}
+
+fn part_footer(part: usize, total: usize) -> String {
+    if part < total {
+        format!(
+            "\n[End of part {} of {}. Do not respond yet: reply only with \"Received part {} of {}\" and wait for the remaining parts.]",
+            part, total, part, total
+        )
+    } else {
+        format!(
+            "\n[End of part {} of {}. All parts have been sent; now respond to the full prompt.]",
+            part, total
+        )
+    }
+}
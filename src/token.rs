@@ -1,8 +1,84 @@
 //! This module encapsulates the logic for counting the tokens in the rendered text.
 
+use crate::model::ModelRegistry;
+use anyhow::{anyhow, Result};
 use colored::*;
+use log::warn;
+use prettytable::{format, row, Table};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
 use tiktoken_rs::{cl100k_base, o200k_base, p50k_base, p50k_edit, r50k_base, CoreBPE};
 
+/// A tokenizer used to count the tokens of the rendered prompt and of each file.
+pub trait Tokenizer: Send + Sync {
+    /// Returns the number of tokens in the text.
+    fn count(&self, text: &str) -> usize;
+}
+
+/// A tokenizer backed by one of the tiktoken BPE encodings.
+pub struct TiktokenTokenizer {
+    bpe: CoreBPE,
+}
+
+impl TiktokenTokenizer {
+    /// Creates a tokenizer for the encoding, falling back to "o200k" for unknown encodings.
+    pub fn new(encoding: &Option<String>) -> Self {
+        let bpe = match normalize_encoding(encoding) {
+            "cl100k" => cl100k_base().unwrap(),
+            "p50k" => p50k_base().unwrap(),
+            "p50k_edit" => p50k_edit().unwrap(),
+            "r50k" => r50k_base().unwrap(),
+            _ => o200k_base().unwrap(),
+        };
+        TiktokenTokenizer { bpe }
+    }
+}
+
+impl Tokenizer for TiktokenTokenizer {
+    fn count(&self, text: &str) -> usize {
+        self.bpe.encode_with_special_tokens(text).len()
+    }
+}
+
+/// A tokenizer loaded from a local `tokenizer.json` file in HuggingFace format.
+pub struct HuggingFaceTokenizer {
+    tokenizer: tokenizers::Tokenizer,
+}
+
+impl HuggingFaceTokenizer {
+    /// Loads the tokenizer from a `tokenizer.json` file.
+    ///
+    /// Truncation and padding configured in the file are disabled so that long texts are
+    /// counted in full.
+    pub fn from_file(path: &Path) -> Result<Self> {
+        let mut tokenizer = tokenizers::Tokenizer::from_file(path)
+            .map_err(|e| anyhow!("無法載入 tokenizer 文件 {:?}: {}", path, e))?;
+        tokenizer
+            .with_truncation(None)
+            .map_err(|e| anyhow!("無法停用 tokenizer 的截斷設定: {}", e))?;
+        tokenizer.with_padding(None);
+        Ok(HuggingFaceTokenizer { tokenizer })
+    }
+}
+
+impl Tokenizer for HuggingFaceTokenizer {
+    /// Returns the number of tokens in the text.
+    ///
+    /// When the text cannot be encoded, the error is logged and its length in bytes is returned
+    /// instead, an upper bound that keeps token budgets and context window checks on the safe
+    /// side.
+    fn count(&self, text: &str) -> usize {
+        match self.tokenizer.encode(text, false) {
+            Ok(encoding) => encoding.len(),
+            Err(e) => {
+                warn!("無法使用 tokenizer 編碼文本，改用字節數估算令牌數: {}", e);
+                text.len()
+            }
+        }
+    }
+}
+
 /// Returns the appropriate tokenizer based on the provided encoding.
 ///
 /// # Arguments
@@ -12,15 +88,29 @@ use tiktoken_rs::{cl100k_base, o200k_base, p50k_base, p50k_edit, r50k_base, Core
 ///
 /// # Returns
 ///
-/// * `CoreBPE` - The tokenizer corresponding to the specified encoding.
-pub fn get_tokenizer(encoding: &Option<String>) -> CoreBPE {
-    match encoding.as_deref().unwrap_or("o200k") {
-        "o200k" => o200k_base().unwrap(),
-        "cl100k" => cl100k_base().unwrap(),
-        "p50k" => p50k_base().unwrap(),
-        "p50k_edit" => p50k_edit().unwrap(),
-        "r50k" | "gpt2" => r50k_base().unwrap(),
-        _ => o200k_base().unwrap(),
+/// * `Box<dyn Tokenizer>` - The tokenizer corresponding to the specified encoding.
+#[allow(clippy::doc_overindented_list_items)]
+pub fn get_tokenizer(encoding: &Option<String>) -> Box<dyn Tokenizer> {
+    Box::new(TiktokenTokenizer::new(encoding))
+}
+
+/// Returns a HuggingFace tokenizer when a `tokenizer.json` file is given, or the tiktoken encoding otherwise.
+///
+/// # Arguments
+///
+/// * `encoding` - An optional string specifying the tiktoken encoding to use.
+/// * `tokenizer_file` - An optional path to a `tokenizer.json` file, which takes precedence over the encoding.
+///
+/// # Returns
+///
+/// * `Result<Box<dyn Tokenizer>>` - The tokenizer, or an error if the file cannot be loaded.
+pub fn load_tokenizer(
+    encoding: &Option<String>,
+    tokenizer_file: &Option<PathBuf>,
+) -> Result<Box<dyn Tokenizer>> {
+    match tokenizer_file {
+        Some(path) => Ok(Box::new(HuggingFaceTokenizer::from_file(path)?)),
+        None => Ok(get_tokenizer(encoding)),
     }
 }
 
@@ -33,15 +123,24 @@ pub fn get_tokenizer(encoding: &Option<String>) -> CoreBPE {
 ///
 /// # Returns
 ///
-/// * `&'static str` - A string describing the models associated with the specified encoding.
-pub fn get_model_info(encoding: &Option<String>) -> &'static str {
+/// * `String` - The names of the built-in models that use the specified encoding.
+#[allow(clippy::doc_overindented_list_items)]
+pub fn get_model_info(encoding: &Option<String>) -> String {
+    ModelRegistry::builtin()
+        .models_for_encoding(normalize_encoding(encoding))
+        .iter()
+        .filter(|model| !model.approximate)
+        .map(|model| model.name.as_str())
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Maps the encoding to one of the supported encoding names, defaulting to "o200k".
+fn normalize_encoding(encoding: &Option<String>) -> &str {
     match encoding.as_deref().unwrap_or("o200k") {
-        "o200k" => "GPT-4o models",
-        "cl100k" => "ChatGPT models, text-embedding-ada-002",
-        "p50k" => "Code models, text-davinci-002, text-davinci-003",
-        "p50k_edit" => "Edit models like text-davinci-edit-001, code-davinci-edit-001",
-        "r50k" | "gpt2" => "GPT-3 models like davinci",
-        _ => "GPT-4o models",
+        encoding @ ("cl100k" | "p50k" | "p50k_edit" | "r50k") => encoding,
+        "gpt2" => "r50k",
+        _ => "o200k",
     }
 }
 
@@ -52,23 +151,9 @@ pub fn get_model_info(encoding: &Option<String>) -> &'static str {
 /// * `rendered` - The rendered template string.
 /// * `encoding` - An optional string specifying the encoding to use for token counting.
 ///                Supported encodings: "o200k" (default), "cl100k", "p50k", "p50k_edit", "r50k", "gpt2".
+#[allow(clippy::doc_overindented_list_items)]
 pub fn count_tokens(rendered: &str, encoding: &Option<String>) {
-    let (bpe, model_info) = match encoding.as_deref().unwrap_or("o200k") {
-        "o200k" => (o200k_base(), "GPT-4o models"),
-        "cl100k" => (cl100k_base(), "ChatGPT models, text-embedding-ada-002"),
-        "p50k" => (
-            p50k_base(),
-            "Code models, text-davinci-002, text-davinci-003",
-        ),
-        "p50k_edit" => (
-            p50k_edit(),
-            "Edit models like text-davinci-edit-001, code-davinci-edit-001",
-        ),
-        "r50k" | "gpt2" => (r50k_base(), "GPT-3 models like davinci"),
-        _ => (o200k_base(), "GPT-4o models"),
-    };
-
-    let token_count = bpe.unwrap().encode_with_special_tokens(rendered).len();
+    let token_count = get_tokenizer(encoding).count(rendered);
 
     println!(
         "{}{}{} Token count: {}, Model info: {}",
@@ -76,6 +161,88 @@ pub fn count_tokens(rendered: &str, encoding: &Option<String>) {
         "i".bold().blue(),
         "]".bold().white(),
         token_count.to_string().bold().yellow(),
-        model_info
+        get_model_info(encoding)
     );
 }
+
+/// Prints a table of the files and directories with the most tokens.
+///
+/// The chunks of a file split by `--max-file-tokens` are added up under its path. Directory
+/// totals include the tokens of every file below the directory.
+///
+/// # Arguments
+///
+/// * `files` - The JSON file representations returned by `traverse_directory`.
+/// * `limit` - The maximum number of files and directories to list.
+pub fn print_token_report(files: &[Value], limit: usize) {
+    let total: usize = files.iter().map(file_tokens).sum();
+
+    let mut heaviest_files: Vec<(&str, usize)> = file_totals(files).into_iter().collect();
+    heaviest_files.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(b.0)));
+
+    let mut table = report_table("File");
+    for (rank, (path, tokens)) in heaviest_files.iter().take(limit).enumerate() {
+        table.add_row(row![r->rank + 1, r->tokens, r->percentage(*tokens, total), path]);
+    }
+    table.printstd();
+
+    let mut heaviest_directories: Vec<(String, usize)> =
+        directory_tokens(files).into_iter().collect();
+    if heaviest_directories.is_empty() {
+        return;
+    }
+    heaviest_directories.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+
+    let mut table = report_table("Directory");
+    for (rank, (path, tokens)) in heaviest_directories.iter().take(limit).enumerate() {
+        table.add_row(row![r->rank + 1, r->tokens, r->percentage(*tokens, total), path]);
+    }
+    table.printstd();
+}
+
+/// Sums the tokens of the chunks of each file.
+fn file_totals(files: &[Value]) -> HashMap<&str, usize> {
+    let mut totals = HashMap::new();
+    for file in files {
+        *totals
+            .entry(file["path"].as_str().unwrap_or(""))
+            .or_insert(0) += file_tokens(file);
+    }
+    totals
+}
+
+/// Sums the tokens of the files below each directory, skipping the root directory.
+fn directory_tokens(files: &[Value]) -> HashMap<String, usize> {
+    let mut directories = HashMap::new();
+    for file in files {
+        let path = Path::new(file["path"].as_str().unwrap_or(""));
+        let tokens = file_tokens(file);
+        for directory in path.ancestors().skip(1) {
+            if directory.parent().is_none_or(|p| p.as_os_str().is_empty()) {
+                break;
+            }
+            *directories
+                .entry(directory.to_string_lossy().into_owned())
+                .or_insert(0) += tokens;
+        }
+    }
+    directories
+}
+
+fn report_table(kind: &str) -> Table {
+    let mut table = Table::new();
+    table.set_format(*format::consts::FORMAT_NO_BORDER_LINE_SEPARATOR);
+    table.set_titles(row!["#", "Tokens", "%", kind]);
+    table
+}
+
+fn file_tokens(file: &Value) -> usize {
+    file["tokens"].as_u64().unwrap_or(0) as usize
+}
+
+fn percentage(tokens: usize, total: usize) -> String {
+    if total == 0 {
+        return "0.0".to_string();
+    }
+    format!("{:.1}", tokens as f64 * 100.0 / total as f64)
+}
@@ -0,0 +1,295 @@
+//! This module contains the construction and rendering of the source tree.
+
+use crate::path::{format_size, EXCLUDED_MARKER};
+use crate::sort::{compare, SortFields, SortKey, SortOrder};
+use anyhow::{anyhow, Result};
+use std::cmp::Ordering;
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use termtree::Tree;
+
+/// A value the entries of the source tree can be annotated with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TreeAnnotation {
+    /// The size in bytes of the file or of the contents of the directory.
+    Size,
+    /// The number of lines of the files in the file list.
+    Lines,
+    /// The number of tokens of the files in the file list.
+    Tokens,
+}
+
+impl FromStr for TreeAnnotation {
+    type Err = anyhow::Error;
+
+    fn from_str(annotation: &str) -> Result<Self> {
+        match annotation.trim().to_ascii_lowercase().as_str() {
+            "size" => Ok(TreeAnnotation::Size),
+            "lines" => Ok(TreeAnnotation::Lines),
+            "tokens" => Ok(TreeAnnotation::Tokens),
+            _ => Err(anyhow!(
+                "未知的源樹註釋 '{}'，可用的註釋: size, lines, tokens",
+                annotation
+            )),
+        }
+    }
+}
+
+/// Options controlling how much of the source tree is rendered and how its entries are labelled.
+#[derive(Debug, Clone, Default)]
+pub struct TreeOptions {
+    /// The depth below the root beyond which the contents of directories are collapsed.
+    pub max_depth: Option<usize>,
+    /// The number of children above which the contents of a directory are collapsed.
+    pub collapse: Option<usize>,
+    /// The values appended to the name of every entry.
+    pub annotations: Vec<TreeAnnotation>,
+}
+
+/// The source tree of a traversal before it is sorted and rendered, so that it can also be
+/// rendered for a subset of its files.
+#[derive(Debug, Clone)]
+pub struct SourceTree {
+    root: TreeNode,
+    sort: SortKey,
+    sort_order: SortOrder,
+    dirs_first: bool,
+    options: TreeOptions,
+}
+
+impl SourceTree {
+    /// Creates the source tree of a root node with the ordering and rendering options.
+    pub(crate) fn new(
+        root: TreeNode,
+        sort: SortKey,
+        sort_order: SortOrder,
+        dirs_first: bool,
+        options: TreeOptions,
+    ) -> Self {
+        SourceTree {
+            root,
+            sort,
+            sort_order,
+            dirs_first,
+            options,
+        }
+    }
+
+    /// Renders the whole tree.
+    pub fn render(&self) -> String {
+        self.render_root(self.root.clone())
+    }
+
+    /// Renders the tree containing only the given files and their parent directories.
+    ///
+    /// # Arguments
+    ///
+    /// * `paths` - The paths of the files relative to the root.
+    ///
+    /// # Returns
+    ///
+    /// * `String` - The rendered tree, sorted, limited and annotated like the whole tree.
+    pub fn render_files(&self, paths: &HashSet<PathBuf>) -> String {
+        let mut root = TreeNode::root(self.root.name.clone());
+        root.children = self
+            .root
+            .children
+            .iter()
+            .filter_map(|child| child.retain_files(paths))
+            .collect();
+        self.render_root(root)
+    }
+
+    fn render_root(&self, mut root: TreeNode) -> String {
+        root.sort(self.sort, self.sort_order, self.dirs_first);
+        root.render(&self.options, 0).to_string()
+    }
+}
+
+/// A node of the source tree before it is rendered.
+#[derive(Debug, Clone)]
+pub(crate) struct TreeNode {
+    name: String,
+    is_dir: bool,
+    /// Whether the entry is left out by the default exclusion profile.
+    pub(crate) excluded_by_default: bool,
+    /// Whether the file is part of the file list.
+    pub(crate) listed: bool,
+    /// The number of lines of the file, or of the listed files of the directory.
+    pub(crate) lines: Option<usize>,
+    /// The fields of the file, or the aggregated fields of the contents of the directory.
+    pub(crate) fields: SortFields,
+    children: Vec<TreeNode>,
+}
+
+impl TreeNode {
+    /// Creates the root node of a tree.
+    pub(crate) fn root(name: String) -> Self {
+        TreeNode::new(name, PathBuf::new(), true)
+    }
+
+    fn new(name: String, path: PathBuf, is_dir: bool) -> Self {
+        TreeNode {
+            name,
+            is_dir,
+            excluded_by_default: false,
+            listed: false,
+            lines: None,
+            fields: SortFields {
+                path,
+                ..SortFields::default()
+            },
+            children: Vec::new(),
+        }
+    }
+
+    /// Returns the node of a relative path, creating it and its missing parents.
+    pub(crate) fn insert(&mut self, relative_path: &Path, is_dir: bool) -> &mut TreeNode {
+        let depth = relative_path.components().count();
+        let mut current = self;
+        let mut path = PathBuf::new();
+        for (index, component) in relative_path.components().enumerate() {
+            path.push(component);
+            let name = component.as_os_str().to_string_lossy().to_string();
+            current = match current.children.iter().position(|child| child.name == name) {
+                Some(position) => &mut current.children[position],
+                None => {
+                    let node = TreeNode::new(name, path.clone(), is_dir || index + 1 < depth);
+                    current.children.push(node);
+                    current.children.last_mut().unwrap()
+                }
+            };
+        }
+        current
+    }
+
+    /// Returns a copy of the node keeping only the given files, or `None` if none is inside it.
+    fn retain_files(&self, paths: &HashSet<PathBuf>) -> Option<TreeNode> {
+        if !self.is_dir {
+            return paths.contains(&self.fields.path).then(|| self.clone());
+        }
+        let children: Vec<TreeNode> = self
+            .children
+            .iter()
+            .filter_map(|child| child.retain_files(paths))
+            .collect();
+        (!children.is_empty()).then(|| TreeNode {
+            name: self.name.clone(),
+            is_dir: true,
+            excluded_by_default: self.excluded_by_default,
+            listed: self.listed,
+            lines: self.lines,
+            fields: self.fields.clone(),
+            children,
+        })
+    }
+
+    /// Aggregates the fields of the directories from their contents and sorts every level.
+    pub(crate) fn sort(&mut self, key: SortKey, order: SortOrder, dirs_first: bool) {
+        if !self.is_dir {
+            return;
+        }
+        let mut lines = 0;
+        for child in &mut self.children {
+            child.sort(key, order, dirs_first);
+            lines += child.lines.unwrap_or(0);
+        }
+        self.lines = Some(lines);
+        // A default-excluded directory holding explicitly included files is not marked
+        self.excluded_by_default &= !self.has_listed_file();
+        self.fields = SortFields::aggregate(
+            std::mem::take(&mut self.fields.path),
+            self.children.iter().map(|child| &child.fields),
+        );
+        self.children.sort_by(|a, b| {
+            let directories = if dirs_first {
+                b.is_dir.cmp(&a.is_dir)
+            } else {
+                Ordering::Equal
+            };
+            directories.then_with(|| compare(&a.fields, &b.fields, key, order))
+        });
+    }
+
+    /// Renders the node and its descendants.
+    ///
+    /// # Arguments
+    ///
+    /// * `options` - The depth, collapsing and annotation options.
+    /// * `depth` - The depth of the node below the root.
+    ///
+    /// # Returns
+    ///
+    /// * `Tree<String>` - The tree, in which collapsed directories contain a single
+    ///   `(+N files)` line.
+    pub(crate) fn render(self, options: &TreeOptions, depth: usize) -> Tree<String> {
+        let mut label = self.name.clone();
+        let annotation = self.annotation(&options.annotations);
+        if !annotation.is_empty() {
+            label = format!("{} ({})", label, annotation);
+        }
+        if self.excluded_by_default {
+            label = format!("{} {}", label, EXCLUDED_MARKER);
+        }
+
+        let collapsed = !self.children.is_empty()
+            && (options
+                .max_depth
+                .is_some_and(|max_depth| depth >= max_depth)
+                || options
+                    .collapse
+                    .is_some_and(|limit| self.children.len() > limit));
+        if collapsed {
+            let files = match self.file_count() {
+                1 => "(+1 file)".to_string(),
+                count => format!("(+{} files)", count),
+            };
+            return Tree::new(label).with_leaves([Tree::new(files)]);
+        }
+        Tree::new(label).with_leaves(
+            self.children
+                .into_iter()
+                .map(|child| child.render(options, depth + 1)),
+        )
+    }
+
+    /// Returns the requested annotations of the node, leaving out the lines and tokens of the
+    /// files outside of the file list.
+    fn annotation(&self, annotations: &[TreeAnnotation]) -> String {
+        annotations
+            .iter()
+            .filter_map(|annotation| match annotation {
+                TreeAnnotation::Size => Some(format_size(self.fields.size)),
+                TreeAnnotation::Lines => {
+                    self.lines
+                        .filter(|_| self.is_dir || self.listed)
+                        .map(|lines| match lines {
+                            1 => "1 line".to_string(),
+                            lines => format!("{} lines", lines),
+                        })
+                }
+                TreeAnnotation::Tokens if self.is_dir || self.listed => {
+                    Some(format!("{} tokens", self.fields.tokens))
+                }
+                TreeAnnotation::Tokens => None,
+            })
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+
+    /// Whether a file of the file list lies inside the node.
+    fn has_listed_file(&self) -> bool {
+        self.children
+            .iter()
+            .any(|child| child.listed || child.has_listed_file())
+    }
+
+    /// Returns the number of files inside the node.
+    fn file_count(&self) -> usize {
+        self.children
+            .iter()
+            .map(|child| if child.is_dir { child.file_count() } else { 1 })
+            .sum()
+    }
+}
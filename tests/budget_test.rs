@@ -0,0 +1,118 @@
+use c2p::budget::{fit_to_budget, CutAction};
+use c2p::token::get_tokenizer;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::Result;
+    use serde_json::{json, Value};
+
+    fn make_file(path: &str, lines: usize) -> Value {
+        let body: String = (0..lines)
+            .map(|i| format!("let value_{} = compute({});\n", i, i))
+            .collect();
+        json!({
+            "path": path,
+            "extension": "rs",
+            "code": format!("```rs\n{}```", body),
+        })
+    }
+
+    fn render(files: &[Value]) -> Result<String> {
+        Ok(files
+            .iter()
+            .map(|file| format!("`{}`:\n\n{}\n", file["path"], file["code"]))
+            .collect())
+    }
+
+    #[test]
+    fn test_fit_to_budget_within_budget() {
+        let tokenizer = get_tokenizer(&None);
+        let mut files = vec![make_file("small.rs", 5)];
+
+        let (_, token_count, cuts) =
+            fit_to_budget(&mut files, 10_000, tokenizer.as_ref(), render).unwrap();
+
+        assert!(token_count <= 10_000);
+        assert!(cuts.is_empty());
+        assert_eq!(files.len(), 1);
+    }
+
+    #[test]
+    fn test_fit_to_budget_truncates_largest_file_first() {
+        let tokenizer = get_tokenizer(&None);
+        let mut files = vec![make_file("small.rs", 5), make_file("large.rs", 500)];
+
+        let (rendered, token_count, cuts) =
+            fit_to_budget(&mut files, 2_000, tokenizer.as_ref(), render).unwrap();
+
+        assert!(token_count <= 2_000);
+        assert_eq!(cuts.len(), 1);
+        assert_eq!(cuts[0].path, "large.rs");
+        assert_eq!(cuts[0].action, CutAction::Truncated);
+        assert!(rendered.contains("[truncated:"));
+        assert!(files[1]["code"].as_str().unwrap().ends_with("```"));
+        assert!(files[0]["truncated"].is_null());
+    }
+
+    #[test]
+    fn test_fit_to_budget_elides_and_drops() {
+        let tokenizer = get_tokenizer(&None);
+        let mut files = vec![
+            make_file("a.rs", 50),
+            make_file("b.rs", 50),
+            make_file("c.rs", 50),
+        ];
+
+        let (_, token_count, cuts) =
+            fit_to_budget(&mut files, 20, tokenizer.as_ref(), render).unwrap();
+
+        assert!(token_count <= 20);
+        assert!(cuts.iter().any(|cut| cut.action == CutAction::Dropped));
+        assert!(files.len() < 3);
+
+        // A dropped file saves the tokens of its rendered header and code, not of its JSON
+        let mut files = vec![json!({ "path": "a.rs", "code": "x", "elided": true })];
+        let (_, _, cuts) = fit_to_budget(&mut files, 0, tokenizer.as_ref(), render).unwrap();
+        assert_eq!(cuts[0].action, CutAction::Dropped);
+        assert_eq!(
+            cuts[0].tokens_saved,
+            tokenizer.count("`a.rs`:\n\n") + tokenizer.count("x")
+        );
+    }
+
+    #[test]
+    fn test_fit_to_budget_spreads_large_overrun() {
+        let tokenizer = get_tokenizer(&None);
+        let mut files: Vec<Value> = (0..6)
+            .map(|i| make_file(&format!("file_{}.rs", i), 200))
+            .collect();
+        let file_tokens = tokenizer.count(files[0]["code"].as_str().unwrap());
+        let total = tokenizer.count(&render(&files).unwrap());
+        // The overrun is larger than any single file, yet every file keeps part of its content
+        let max_tokens = total - file_tokens * 3 / 2;
+
+        let (_, token_count, cuts) =
+            fit_to_budget(&mut files, max_tokens, tokenizer.as_ref(), render).unwrap();
+
+        assert!(token_count <= max_tokens);
+        assert_eq!(cuts.len(), 6);
+        assert!(cuts.iter().all(|cut| cut.action == CutAction::Truncated));
+        assert!(files.iter().all(|file| file["elided"].is_null()));
+    }
+
+    #[test]
+    fn test_fit_to_budget_impossible() {
+        let tokenizer = get_tokenizer(&None);
+        let mut files = vec![make_file("a.rs", 50)];
+
+        let result = fit_to_budget(&mut files, 1, tokenizer.as_ref(), |files| {
+            Ok(format!(
+                "A fixed header that never fits. {}",
+                render(files)?
+            ))
+        });
+
+        assert!(result.is_err());
+    }
+}
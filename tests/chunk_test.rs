@@ -0,0 +1,214 @@
+use c2p::chunk::{chunk_code, matching_regions, symbol_definitions};
+use c2p::token::get_tokenizer;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn make_rust_source(functions: usize) -> String {
+        (0..functions)
+            .map(|i| {
+                format!(
+                    "/// Computes value {i}.\n#[inline]\npub fn compute_{i}(x: u32) -> u32 {{\n    let y = x + {i};\n    y * 2\n}}\n\n"
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_chunk_code_splits_at_definitions() {
+        let tokenizer = get_tokenizer(&None);
+        let code = make_rust_source(20);
+
+        let chunks = chunk_code(&code, "rs", 100, tokenizer.as_ref());
+
+        assert!(chunks.len() > 1);
+        assert_eq!(chunks[0].start_line, 1);
+        assert_eq!(chunks.last().unwrap().end_line, code.lines().count());
+        for pair in chunks.windows(2) {
+            assert_eq!(pair[0].end_line + 1, pair[1].start_line);
+        }
+        for chunk in &chunks {
+            assert!(tokenizer.count(&chunk.code) <= 100);
+            // Doc comments and attributes stay with the function below them.
+            assert!(chunk.code.starts_with("/// Computes value"));
+        }
+    }
+
+    #[test]
+    fn test_chunk_code_splits_large_class_at_methods() {
+        let tokenizer = get_tokenizer(&None);
+        let methods: String = (0..30)
+            .map(|i| format!("    def method_{i}(self):\n        return self.value + {i}\n\n"))
+            .collect();
+        let code = format!("class Large:\n{}", methods);
+
+        let chunks = chunk_code(&code, "py", 60, tokenizer.as_ref());
+
+        assert!(chunks.len() > 1);
+        for chunk in &chunks[1..] {
+            assert!(chunk.code.starts_with("    def method_"));
+        }
+    }
+
+    #[test]
+    fn test_chunk_code_falls_back_to_lines() {
+        let tokenizer = get_tokenizer(&None);
+        let code: String = (0..200).map(|i| format!("value_{i} = {i}\n")).collect();
+
+        let chunks = chunk_code(&code, "txt", 50, tokenizer.as_ref());
+
+        assert!(chunks.len() > 1);
+        let lines: usize = chunks.iter().map(|c| c.end_line - c.start_line + 1).sum();
+        assert_eq!(lines, 200);
+        for chunk in &chunks {
+            assert!(tokenizer.count(&chunk.code) <= 50);
+        }
+    }
+
+    #[test]
+    fn test_matching_regions_with_context() {
+        let code: String = (1..=30)
+            .map(|i| match i {
+                5 | 7 | 25 => format!("line {i}: panic!(\"connection reset\")\n"),
+                _ => format!("line {i}\n"),
+            })
+            .collect();
+        let regex = regex::Regex::new("connection reset").unwrap();
+
+        let regions = matching_regions(&code, &regex, 2);
+
+        // The matches on lines 5 and 7 overlap once their context is added
+        assert_eq!(regions.len(), 2);
+        assert_eq!((regions[0].start_line, regions[0].end_line), (3, 9));
+        assert_eq!((regions[1].start_line, regions[1].end_line), (23, 27));
+        assert!(regions[1].code.starts_with("line 23"));
+
+        let regions = matching_regions(&code, &regex, 0);
+        assert_eq!(regions.len(), 3);
+        assert_eq!((regions[2].start_line, regions[2].end_line), (25, 25));
+
+        let regex = regex::Regex::new("no such text").unwrap();
+        assert!(matching_regions(&code, &regex, 2).is_empty());
+    }
+
+    #[test]
+    fn test_symbol_definitions_in_rust() {
+        let code = "\
+use std::fmt;
+
+/// A point.
+#[derive(Debug)]
+pub struct Point {
+    x: i32,
+}
+
+pub struct Marker;
+
+impl fmt::Display for Point {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, \"{}\", self.x)
+    }
+}
+
+pub fn distance(a: &Point) -> i32 {
+    a.x
+}
+";
+        let symbols = |names: &[&str]| {
+            let names: Vec<String> = names.iter().map(|name| name.to_string()).collect();
+            symbol_definitions(code, "rs", &names)
+                .into_iter()
+                .map(|chunk| (chunk.start_line, chunk.end_line))
+                .collect::<Vec<_>>()
+        };
+
+        // The struct comes with its doc comment and attributes, and its impl block
+        assert_eq!(symbols(&["Point"]), vec![(3, 7), (11, 15)]);
+        assert_eq!(symbols(&["Marker", "distance"]), vec![(9, 9), (17, 19)]);
+        // Methods nested in impl blocks are found, and adjacent spans stay separate
+        assert_eq!(symbols(&["fmt", "distance"]), vec![(12, 14), (17, 19)]);
+        assert!(symbols(&["missing"]).is_empty());
+    }
+
+    #[test]
+    fn test_symbol_definitions_skip_braces_in_literals_and_comments() {
+        let code = "\
+fn open_brace(c: char) -> bool {
+    c == '{'
+}
+
+fn unrelated() {
+    println!(\"}}\");
+}
+
+fn lifetime<'a>(text: &'a str) -> &'a str {
+    // } is not the end
+    let raw = r#\"{\"#;
+    /* { */
+    text
+}
+
+fn quotes() -> [char; 2] {
+    ['\\'','{']
+}
+
+fn after() {}
+";
+        let symbols = |name: &str| {
+            symbol_definitions(code, "rs", &[name.to_string()])
+                .into_iter()
+                .map(|chunk| (chunk.start_line, chunk.end_line))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(symbols("open_brace"), vec![(1, 3)]);
+        assert_eq!(symbols("unrelated"), vec![(5, 7)]);
+        assert_eq!(symbols("lifetime"), vec![(9, 14)]);
+        assert_eq!(symbols("quotes"), vec![(16, 18)]);
+
+        let javascript = "function quote() {\n  return '}' + `${x}}`;\n}\n\nfunction next() {}\n";
+        let chunks = symbol_definitions(javascript, "js", &["quote".to_string()]);
+        assert_eq!((chunks[0].start_line, chunks[0].end_line), (1, 3));
+    }
+
+    #[test]
+    fn test_symbol_definitions_in_other_languages() {
+        let python = "\
+import os
+
+@dataclass
+class Config:
+    name: str
+
+    def load(
+        self,
+    ):
+        return os.environ
+
+def main():
+    pass
+";
+        let names = vec!["Config".to_string()];
+        let chunks = symbol_definitions(python, "py", &names);
+        assert_eq!(chunks.len(), 1);
+        assert_eq!((chunks[0].start_line, chunks[0].end_line), (3, 10));
+        assert!(chunks[0].code.starts_with("@dataclass"));
+
+        let go =
+            "package main\n\ntype ID int\n\nfunc (s *Server) Serve() error {\n\treturn nil\n}\n";
+        let names = vec!["ID".to_string(), "Serve".to_string()];
+        let chunks = symbol_definitions(go, "go", &names);
+        assert_eq!(
+            chunks
+                .iter()
+                .map(|chunk| (chunk.start_line, chunk.end_line))
+                .collect::<Vec<_>>(),
+            vec![(3, 3), (5, 7)]
+        );
+
+        let ruby = "module Api\n  def fetch\n    get\n  end\nend\n";
+        let names = vec!["fetch".to_string()];
+        let chunks = symbol_definitions(ruby, "rb", &names);
+        assert_eq!((chunks[0].start_line, chunks[0].end_line), (2, 4));
+    }
+}
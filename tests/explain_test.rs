@@ -0,0 +1,82 @@
+use c2p::explain::{explain_directory, ExplainReason};
+use c2p::filter::{FilterReason, RuleAction};
+use c2p::path::TraverseOptions;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::path::Path;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_explain_directory_reasons() {
+        let dir = tempdir().unwrap();
+        fs::create_dir_all(dir.path().join("src")).unwrap();
+        fs::create_dir_all(dir.path().join("build")).unwrap();
+        fs::write(dir.path().join(".c2pignore"), "build/\n").unwrap();
+        fs::write(dir.path().join("build/output.rs"), "fn output() {}\n").unwrap();
+        fs::write(dir.path().join("src/main.rs"), "fn main() {}\n").unwrap();
+        fs::write(dir.path().join("src/empty.rs"), "\n").unwrap();
+        fs::write(dir.path().join("src/data.rs"), [0xff, 0xfe, 0x00]).unwrap();
+        fs::write(dir.path().join("notes.txt"), "notes\n").unwrap();
+        fs::write(dir.path().join("Cargo.lock"), "version = 3\n").unwrap();
+        fs::write(
+            dir.path().join("src/schema.rs"),
+            "// Code generated by schema. DO NOT EDIT.\npub struct Schema;\n",
+        )
+        .unwrap();
+
+        let options = TraverseOptions {
+            include: vec!["*.rs".to_string()],
+            ..TraverseOptions::default()
+        };
+        let explanations = explain_directory(dir.path(), &options).unwrap();
+        let reason = |path: &str| {
+            let explanation = explanations
+                .iter()
+                .find(|explanation| explanation.path == Path::new(path))
+                .unwrap_or_else(|| panic!("no explanation for {}", path));
+            (explanation.included, explanation.reason.clone())
+        };
+
+        assert_eq!(
+            reason("src/main.rs"),
+            (
+                true,
+                ExplainReason::Filter(FilterReason::Pattern {
+                    action: RuleAction::Include,
+                    pattern: "*.rs".to_string()
+                })
+            )
+        );
+        assert_eq!(reason("src/empty.rs"), (false, ExplainReason::Empty));
+        assert_eq!(reason("src/data.rs"), (false, ExplainReason::NotUtf8));
+        assert_eq!(
+            reason("notes.txt"),
+            (false, ExplainReason::Filter(FilterReason::NoIncludeMatch))
+        );
+        assert_eq!(reason(".c2pignore"), (false, ExplainReason::Hidden));
+        assert_eq!(
+            reason("Cargo.lock"),
+            (
+                false,
+                ExplainReason::DefaultExclude {
+                    pattern: "Cargo.lock".to_string()
+                }
+            )
+        );
+        assert_eq!(reason("src/schema.rs"), (false, ExplainReason::Generated));
+
+        // Ignored directories are reported once, with the rule that skipped them
+        let (included, ExplainReason::Ignored { source, pattern }) = reason("build") else {
+            panic!("build/ is not explained as ignored");
+        };
+        assert!(!included);
+        assert_eq!(pattern.as_deref(), Some("build/"));
+        assert!(source.unwrap().ends_with(".c2pignore"));
+        assert!(explanations
+            .iter()
+            .all(|explanation| explanation.path != Path::new("build/output.rs")));
+    }
+}
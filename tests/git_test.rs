@@ -1,4 +1,7 @@
-use c2p::git::{get_git_diff, get_git_diff_between_branches};
+use c2p::git::{
+    get_files_by_status, get_files_changed_since, get_git_diff, get_git_diff_between_branches,
+    get_last_commit_times, GitFileStatus,
+};
 
 #[cfg(test)]
 mod tests {
@@ -253,4 +256,159 @@ mod tests {
         )
         .expect("Failed to commit second change in new branch");
     }
+
+    /// Creates a repository with one commit of `committed.txt` and `src/other.txt`.
+    fn init_repo_with_commit(repo_path: &std::path::Path) -> Repository {
+        let repo = Repository::init(repo_path).expect("Failed to initialize repository");
+        fs::create_dir_all(repo_path.join("src")).expect("Failed to create directory");
+        fs::write(repo_path.join("committed.txt"), "Initial content")
+            .expect("Failed to write to test file");
+        fs::write(repo_path.join("src/other.txt"), "Other content")
+            .expect("Failed to write to test file");
+
+        let mut index = repo.index().expect("Failed to get repository index");
+        for path in ["committed.txt", "src/other.txt"] {
+            index
+                .add_path(std::path::Path::new(path))
+                .expect("Failed to add file to index");
+        }
+        index.write().expect("Failed to write index");
+
+        let tree_id = index.write_tree().expect("Failed to write tree");
+        let signature =
+            Signature::now("Test", "test@example.com").expect("Failed to create signature");
+        {
+            let tree = repo.find_tree(tree_id).expect("Failed to find tree");
+            repo.commit(
+                Some("HEAD"),
+                &signature,
+                &signature,
+                "Initial commit",
+                &tree,
+                &[],
+            )
+            .expect("Failed to commit");
+        }
+        repo
+    }
+
+    #[test]
+    fn test_get_files_by_status() {
+        let temp_dir = TempDir::new().expect("Failed to create temp dir");
+        let repo_path = temp_dir.path();
+        let repo = init_repo_with_commit(repo_path);
+
+        // Modify a committed file, stage a new file and leave another one untracked
+        fs::write(repo_path.join("committed.txt"), "Modified content")
+            .expect("Failed to modify test file");
+        fs::write(repo_path.join("src/staged.txt"), "Staged content")
+            .expect("Failed to write to test file");
+        let mut index = repo.index().expect("Failed to get repository index");
+        index
+            .add_path(std::path::Path::new("src/staged.txt"))
+            .expect("Failed to add file to index");
+        index.write().expect("Failed to write index");
+        fs::write(repo_path.join("untracked.txt"), "Untracked content")
+            .expect("Failed to write to test file");
+
+        let modified = get_files_by_status(repo_path, &[GitFileStatus::Modified]).unwrap();
+        assert_eq!(modified.len(), 1);
+        assert!(modified.contains(std::path::Path::new("committed.txt")));
+
+        let changed = get_files_by_status(
+            repo_path,
+            &[GitFileStatus::Staged, GitFileStatus::Untracked],
+        )
+        .unwrap();
+        assert_eq!(changed.len(), 2);
+        assert!(changed.contains(std::path::Path::new("src/staged.txt")));
+        assert!(changed.contains(std::path::Path::new("untracked.txt")));
+
+        // Paths are relative to the traversal root, which may be a subdirectory
+        let in_src = get_files_by_status(&repo_path.join("src"), &[GitFileStatus::Staged]).unwrap();
+        assert!(in_src.contains(std::path::Path::new("staged.txt")));
+
+        assert!("deleted".parse::<GitFileStatus>().is_err());
+    }
+
+    #[test]
+    fn test_get_files_changed_since() {
+        let temp_dir = TempDir::new().expect("Failed to create temp dir");
+        let repo_path = temp_dir.path();
+        init_repo_with_commit(repo_path);
+
+        fs::write(repo_path.join("committed.txt"), "Modified content")
+            .expect("Failed to modify test file");
+        fs::write(repo_path.join("untracked.txt"), "Untracked content")
+            .expect("Failed to write to test file");
+
+        let changed = get_files_changed_since(repo_path, "HEAD").unwrap();
+        assert_eq!(changed.len(), 2);
+        assert!(changed.contains(std::path::Path::new("committed.txt")));
+        assert!(changed.contains(std::path::Path::new("untracked.txt")));
+
+        // A date after the last commit compares against that commit
+        let changed = get_files_changed_since(repo_path, "2999-01-01").unwrap();
+        assert_eq!(changed.len(), 2);
+
+        // A date before the first commit counts every file as changed
+        let changed = get_files_changed_since(repo_path, "2000-01-01").unwrap();
+        assert_eq!(changed.len(), 3);
+        assert!(changed.contains(std::path::Path::new("src/other.txt")));
+
+        assert!(get_files_changed_since(repo_path, "no-such-branch").is_err());
+    }
+
+    #[test]
+    fn test_get_last_commit_times() {
+        let temp_dir = TempDir::new().expect("Failed to create temp dir");
+        let repo_path = temp_dir.path();
+        let repo = init_repo_with_commit(repo_path);
+
+        // Commit a change to one of the files at a later time
+        fs::write(repo_path.join("committed.txt"), "Modified content")
+            .expect("Failed to modify test file");
+        let mut index = repo.index().expect("Failed to get repository index");
+        index
+            .add_path(std::path::Path::new("committed.txt"))
+            .expect("Failed to add file to index");
+        index.write().expect("Failed to write index");
+        let tree_id = index.write_tree().expect("Failed to write tree");
+        let tree = repo.find_tree(tree_id).expect("Failed to find tree");
+        let parent = repo
+            .head()
+            .and_then(|head| head.peel_to_commit())
+            .expect("Failed to find HEAD");
+        let later = parent.time().seconds() + 3600;
+        let signature = Signature::new("Test", "test@example.com", &git2::Time::new(later, 0))
+            .expect("Failed to create signature");
+        repo.commit(
+            Some("HEAD"),
+            &signature,
+            &signature,
+            "Second commit",
+            &tree,
+            &[&parent],
+        )
+        .expect("Failed to commit");
+
+        let paths = ["committed.txt", "src/other.txt", "untracked.txt"]
+            .into_iter()
+            .map(std::path::PathBuf::from)
+            .collect();
+        let times = get_last_commit_times(repo_path, &paths).unwrap();
+        assert_eq!(times.len(), 2);
+        assert_eq!(times[std::path::Path::new("committed.txt")], later);
+        assert_eq!(
+            times[std::path::Path::new("src/other.txt")],
+            parent.time().seconds()
+        );
+
+        // Paths are relative to the traversal root, which may be a subdirectory
+        let paths = [std::path::PathBuf::from("other.txt")]
+            .into_iter()
+            .collect();
+        let times = get_last_commit_times(&repo_path.join("src"), &paths).unwrap();
+        assert!(times.contains_key(std::path::Path::new("other.txt")));
+    }
 }
@@ -0,0 +1,114 @@
+use c2p::group::{group_file_name, group_files, render_index, ManifestEntry, ROOT_GROUP};
+use c2p::path::{label, traverse_directory_tree, TraverseOptions};
+use c2p::token::get_tokenizer;
+use c2p::tree::TreeOptions;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::{json, Value};
+    use std::fs;
+    use tempfile::tempdir;
+
+    fn make_files(paths: &[&str]) -> Vec<Value> {
+        paths
+            .iter()
+            .map(|path| json!({ "path": path, "code": "code" }))
+            .collect()
+    }
+
+    #[test]
+    fn test_group_files_by_depth() {
+        let files = make_files(&[
+            "repo/README.md",
+            "repo/src/main.rs",
+            "repo/src/core/lib.rs",
+            "repo/tests/it.rs",
+            "repo/src/core/util.rs",
+        ]);
+
+        let groups = group_files(&files, 1);
+        let names: Vec<&str> = groups.iter().map(|group| group.name.as_str()).collect();
+        assert_eq!(names, vec![ROOT_GROUP, "src", "tests"]);
+        assert_eq!(groups[1].files.len(), 3);
+
+        let groups = group_files(&files, 2);
+        let names: Vec<&str> = groups.iter().map(|group| group.name.as_str()).collect();
+        assert_eq!(names, vec![ROOT_GROUP, "src", "src/core", "tests"]);
+        assert_eq!(groups[2].files.len(), 2);
+    }
+
+    #[test]
+    fn test_group_source_tree_and_file_name() {
+        let dir = tempdir().unwrap();
+        fs::create_dir_all(dir.path().join("src/core")).unwrap();
+        fs::write(dir.path().join("src/core/lib.rs"), "pub fn lib() {}\n").unwrap();
+        fs::write(dir.path().join("src/core/util.rs"), "pub fn util() {}\n").unwrap();
+        fs::write(dir.path().join("README.md"), "# Title\n").unwrap();
+        let tokenizer = get_tokenizer(&None);
+        let (tree, files) =
+            traverse_directory_tree(dir.path(), &TraverseOptions::default(), tokenizer.as_ref())
+                .unwrap();
+        let group = &group_files(&files, 2)[1];
+        assert_eq!(group.name, "src/core");
+
+        let source_tree = group.source_tree(&tree);
+        assert!(source_tree.starts_with(&label(dir.path())));
+        assert!(source_tree.contains("lib.rs"));
+        assert!(source_tree.contains("util.rs"));
+        assert!(!source_tree.contains("README.md"));
+
+        assert_eq!(group_file_name(&group.name, 3), "003-src-core.md");
+        assert_eq!(group_file_name(ROOT_GROUP, 1), "001-root.md");
+    }
+
+    #[test]
+    fn test_group_source_tree_follows_tree_depth() {
+        let dir = tempdir().unwrap();
+        fs::create_dir_all(dir.path().join("src/core")).unwrap();
+        fs::write(dir.path().join("src/core/lib.rs"), "pub fn lib() {}\n").unwrap();
+        fs::write(dir.path().join("src/core/util.rs"), "pub fn util() {}\n").unwrap();
+        let options = TraverseOptions {
+            tree: TreeOptions {
+                max_depth: Some(1),
+                ..TreeOptions::default()
+            },
+            ..TraverseOptions::default()
+        };
+        let tokenizer = get_tokenizer(&None);
+        let (tree, files) =
+            traverse_directory_tree(dir.path(), &options, tokenizer.as_ref()).unwrap();
+        let group = &group_files(&files, 2)[0];
+
+        let source_tree = group.source_tree(&tree);
+        assert!(source_tree.contains("src"));
+        assert!(source_tree.contains("(+2 files)"));
+        assert!(!source_tree.contains("lib.rs"));
+        assert_eq!(source_tree, tree.render());
+    }
+
+    #[test]
+    fn test_render_index() {
+        let manifest = vec![
+            ManifestEntry {
+                file: "001-src.md".to_string(),
+                group: "src".to_string(),
+                files: 2,
+                tokens: 120,
+            },
+            ManifestEntry {
+                file: "002-tests.md".to_string(),
+                group: "tests".to_string(),
+                files: 1,
+                tokens: 40,
+            },
+        ];
+
+        let index = render_index("repo", "repo\n└── src", &manifest).unwrap();
+
+        assert!(index.contains("Project Path: repo"));
+        assert!(index.contains("└── src"));
+        assert!(index.contains("- `001-src.md`: `src` (2 files, 120 tokens)"));
+        assert!(index.contains("- `002-tests.md`: `tests` (1 file, 40 tokens)"));
+    }
+}
@@ -0,0 +1,175 @@
+use c2p::imports::reachable_files;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+    use std::fs;
+    use std::path::{Path, PathBuf};
+    use tempfile::tempdir;
+
+    fn write_files(root: &Path, files: &[(&str, &str)]) {
+        for (path, content) in files {
+            let path = root.join(path);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, content).unwrap();
+        }
+    }
+
+    fn paths(paths: &[&str]) -> HashSet<PathBuf> {
+        paths.iter().map(PathBuf::from).collect()
+    }
+
+    #[test]
+    fn test_reachable_rust_files() {
+        let dir = tempdir().unwrap();
+        write_files(
+            dir.path(),
+            &[
+                ("Cargo.toml", "[package]\nname = \"my-tool\"\n"),
+                ("src/main.rs", "mod cli;\nfn main() { my_tool::run(); }\n"),
+                (
+                    "src/cli.rs",
+                    "use crate::config::{self, Config as Settings};\n",
+                ),
+                ("src/config/mod.rs", "pub mod parser;\n"),
+                ("src/config/parser.rs", "pub fn parse() {}\n"),
+                ("src/lib.rs", "pub fn run() {}\n"),
+                ("src/unused.rs", "pub fn unused() {}\n"),
+            ],
+        );
+
+        let reachable = reachable_files(dir.path(), Path::new("src/main.rs"), None).unwrap();
+        assert_eq!(
+            reachable,
+            paths(&[
+                "src/main.rs",
+                "src/cli.rs",
+                "src/config/mod.rs",
+                "src/config/parser.rs",
+                "src/lib.rs",
+            ])
+        );
+
+        let reachable = reachable_files(dir.path(), Path::new("src/main.rs"), Some(1)).unwrap();
+        assert_eq!(
+            reachable,
+            paths(&["src/main.rs", "src/cli.rs", "src/lib.rs"])
+        );
+
+        assert!(reachable_files(dir.path(), Path::new("src/missing.rs"), None).is_err());
+    }
+
+    #[test]
+    fn test_reachable_rust_files_from_other_crate_roots() {
+        let dir = tempdir().unwrap();
+        write_files(
+            dir.path(),
+            &[
+                (
+                    "Cargo.toml",
+                    "[package]\nname = \"my-tool\"\n\n[[bin]]\nname = \"other\"\npath = \"tools/other.rs\"\n",
+                ),
+                ("src/lib.rs", "pub mod config;\n"),
+                ("src/config.rs", "pub fn load() {}\n"),
+                (
+                    "src/bin/tool.rs",
+                    "mod helper;\nfn main() { crate::helper::run(); my_tool::config::load(); }\n",
+                ),
+                ("src/bin/helper.rs", "pub fn run() { crate::shared::run(); }\n"),
+                ("src/bin/shared.rs", "pub fn run() {}\n"),
+                ("examples/demo.rs", "mod util;\nfn main() {}\n"),
+                ("examples/util.rs", "pub fn util() {}\n"),
+                ("build.rs", "mod codegen;\nfn main() {}\n"),
+                ("codegen.rs", "pub fn generate() {}\n"),
+                ("tools/other.rs", "mod common;\nfn main() {}\n"),
+                ("tools/common.rs", "pub fn common() {}\n"),
+            ],
+        );
+
+        let reachable = reachable_files(dir.path(), Path::new("src/bin/tool.rs"), None).unwrap();
+        assert_eq!(
+            reachable,
+            paths(&[
+                "src/bin/tool.rs",
+                "src/bin/helper.rs",
+                "src/bin/shared.rs",
+                "src/lib.rs",
+                "src/config.rs",
+            ])
+        );
+
+        let reachable = reachable_files(dir.path(), Path::new("examples/demo.rs"), None).unwrap();
+        assert_eq!(reachable, paths(&["examples/demo.rs", "examples/util.rs"]));
+
+        let reachable = reachable_files(dir.path(), Path::new("build.rs"), None).unwrap();
+        assert_eq!(reachable, paths(&["build.rs", "codegen.rs"]));
+
+        let reachable = reachable_files(dir.path(), Path::new("tools/other.rs"), None).unwrap();
+        assert_eq!(reachable, paths(&["tools/other.rs", "tools/common.rs"]));
+    }
+
+    #[test]
+    fn test_reachable_python_files() {
+        let dir = tempdir().unwrap();
+        write_files(
+            dir.path(),
+            &[
+                (
+                    "app.py",
+                    "import os\nimport pkg.models as models\nfrom pkg import (\n    views,\n)\n",
+                ),
+                ("pkg/__init__.py", ""),
+                ("pkg/models.py", "from .base import Model\n"),
+                ("pkg/base.py", "class Model: pass\n"),
+                ("pkg/views.py", "from . import helpers\n"),
+                ("pkg/helpers.py", "def helper(): pass\n"),
+                ("pkg/unused.py", "def unused(): pass\n"),
+            ],
+        );
+
+        let reachable = reachable_files(dir.path(), Path::new("app.py"), None).unwrap();
+        assert_eq!(
+            reachable,
+            paths(&[
+                "app.py",
+                "pkg/__init__.py",
+                "pkg/models.py",
+                "pkg/base.py",
+                "pkg/views.py",
+                "pkg/helpers.py",
+            ])
+        );
+    }
+
+    #[test]
+    fn test_reachable_javascript_files() {
+        let dir = tempdir().unwrap();
+        write_files(
+            dir.path(),
+            &[
+                (
+                    "src/index.ts",
+                    "import { api } from './api.js';\nimport type {\n  Config,\n} from \"../config\";\nimport 'lodash';\n",
+                ),
+                ("src/api.ts", "const util = require('./util');\nexport * from './types';\n"),
+                ("src/util/index.js", "module.exports = {};\n"),
+                ("src/types.d.ts", "export type Id = string;\n"),
+                ("config.ts", "export interface Config {}\n"),
+                ("src/unused.ts", "export {};\n"),
+            ],
+        );
+
+        let reachable = reachable_files(dir.path(), Path::new("src/index.ts"), None).unwrap();
+        assert_eq!(
+            reachable,
+            paths(&[
+                "src/index.ts",
+                "src/api.ts",
+                "src/util/index.js",
+                "src/types.d.ts",
+                "config.ts",
+            ])
+        );
+    }
+}
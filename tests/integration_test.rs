@@ -24,11 +24,13 @@ fn init_logger() {
 fn create_temp_file(dir: &Path, name: &str, content: &str) {
     let file_path = dir.join(name);
     let parent_dir = file_path.parent().unwrap();
-    fs::create_dir_all(parent_dir).expect(&format!("Failed to create directory: {:?}", parent_dir));
-    let mut file =
-        File::create(&file_path).expect(&format!("Failed to create temp file: {:?}", file_path));
+    fs::create_dir_all(parent_dir)
+        .unwrap_or_else(|_| panic!("Failed to create directory: {:?}", parent_dir));
+    let mut file = File::create(&file_path)
+        .unwrap_or_else(|_| panic!("Failed to create temp file: {:?}", file_path));
     //debug!("Writing to file: {:?}", file_path);
-    writeln!(file, "{}", content).expect(&format!("Failed to write to temp file: {:?}", file_path));
+    writeln!(file, "{}", content)
+        .unwrap_or_else(|_| panic!("Failed to write to temp file: {:?}", file_path));
 }
 
 fn create_test_hierarchy(base_path: &Path) {
@@ -67,7 +69,8 @@ fn create_test_hierarchy(base_path: &Path) {
 
 fn read_output_file(dir: &Path, file_name: &str) -> String {
     let file_path = dir.join(file_name);
-    read_to_string(&file_path).expect(&format!("Failed to read output file: {:?}", file_path))
+    read_to_string(&file_path)
+        .unwrap_or_else(|_| panic!("Failed to read output file: {:?}", file_path))
 }
 
 mod tests {
@@ -89,11 +92,16 @@ mod tests {
         }
 
         fn command(&self) -> Command {
+            let mut cmd = self.map_reduce_command();
+            cmd.arg("--output").arg(&self.output_file);
+            cmd
+        }
+
+        /// Returns the command without `--output`, which conflicts with `--map-reduce`.
+        fn map_reduce_command(&self) -> Command {
             let mut cmd = Command::cargo_bin("c2p").expect("Failed to find code2prompt binary");
             cmd.arg("path")
-                .arg(&self.dir.path().to_str().unwrap())
-                .arg("--output")
-                .arg(&self.output_file)
+                .arg(self.dir.path().to_str().unwrap())
                 .arg("--lang=en")
                 .arg("--no-clipboard");
             cmd
@@ -253,4 +261,342 @@ mod tests {
         assert!(contains("BAZ.py").eval(&output));
         assert!(contains("CONTENT BAZ.PY").eval(&output));
     }
+
+    #[test]
+    fn test_max_tokens_truncates_large_file() {
+        let env = TestEnv::new();
+        let large_content: String = (0..2000)
+            .map(|i| format!("line number {} of the large file\n", i))
+            .collect();
+        create_temp_file(env.dir.path(), "lowercase/large.txt", &large_content);
+
+        let mut cmd = env.command();
+        cmd.arg("--max-tokens=1000").assert().success();
+
+        let output = env.read_output();
+        debug!("Test max tokens output:\n{}", output);
+        assert!(contains("content foo.py").eval(&output));
+        assert!(contains("line number 0 of the large file").eval(&output));
+        assert!(contains("[truncated:").eval(&output));
+        assert!(contains("line number 1999 of the large file")
+            .not()
+            .eval(&output));
+    }
+
+    #[test]
+    fn test_token_report() {
+        let env = TestEnv::new();
+        let mut cmd = env.command();
+        cmd.arg("--token-report=3")
+            .assert()
+            .success()
+            .stdout(contains("Tokens"))
+            .stdout(contains("Directory"))
+            .stdout(contains("/lowercase"));
+    }
+
+    #[test]
+    fn test_chunked_file_listed_once() {
+        let env = TestEnv::new();
+        let large_content: String = (0..40)
+            .map(|i| {
+                format!(
+                    "fn function_{}() {{\n    println!(\"function {}\");\n}}\n\n",
+                    i, i
+                )
+            })
+            .collect();
+        create_temp_file(env.dir.path(), "lowercase/large.rs", &large_content);
+
+        let mut cmd = env.command();
+        let output = cmd
+            .arg("--max-file-tokens=100")
+            .arg("--token-report=20")
+            .arg("--json")
+            .assert()
+            .success()
+            .get_output()
+            .stdout
+            .clone();
+        let report = String::from_utf8_lossy(&output);
+        assert_eq!(report.matches("lowercase/large.rs").count(), 1);
+
+        let output: serde_json::Value = serde_json::from_str(&env.read_output()).unwrap();
+        assert!(contains("Lines 1-").eval(output["prompt"].as_str().unwrap()));
+        let files = output["files"].as_array().unwrap();
+        assert_eq!(files.len(), 13);
+        assert_eq!(
+            files
+                .iter()
+                .filter(|path| path.as_str().unwrap().ends_with("large.rs"))
+                .count(),
+            1
+        );
+
+        let parts_dir = env.dir.path().join("parts");
+        let mut cmd = env.map_reduce_command();
+        cmd.arg("--max-file-tokens=100")
+            .arg("--map-reduce")
+            .arg(&parts_dir)
+            .assert()
+            .success();
+        let index = read_output_file(&parts_dir, "000-index.md");
+        assert!(contains("`lowercase` (7 files,").eval(&index));
+    }
+
+    #[test]
+    fn test_map_reduce_conflicts_with_output() {
+        let env = TestEnv::new();
+        let mut cmd = env.command();
+        cmd.arg("--map-reduce")
+            .arg(env.dir.path().join("parts"))
+            .assert()
+            .failure()
+            .stderr(contains("cannot be used with"));
+    }
+
+    #[test]
+    fn test_model_context_window_overflow() {
+        let env = TestEnv::new();
+        let large_content: String = (0..2000)
+            .map(|i| format!("line number {} of the large file\n", i))
+            .collect();
+        create_temp_file(env.dir.path(), "lowercase/large.txt", &large_content);
+
+        let mut cmd = env.command();
+        cmd.arg("--model=gpt-4")
+            .assert()
+            .success()
+            .stdout(contains("gpt-4 的上下文窗口"));
+
+        let mut cmd = env.command();
+        cmd.arg("--model=gpt-4")
+            .arg("--fail-on-overflow")
+            .assert()
+            .failure();
+
+        // Each part of a split prompt is checked against the context window
+        let mut cmd = env.command();
+        cmd.arg("--model=gpt-4")
+            .arg("--fail-on-overflow")
+            .arg("--split=4000")
+            .assert()
+            .success();
+
+        // Each map-reduce group prompt is checked against the context window
+        let mut cmd = env.map_reduce_command();
+        cmd.arg("--model=gpt-4")
+            .arg("--fail-on-overflow")
+            .arg("--map-reduce")
+            .arg(env.dir.path().join("parts"))
+            .assert()
+            .failure();
+    }
+
+    #[test]
+    fn test_json_output_with_costs() {
+        let env = TestEnv::new();
+        let mut cmd = env.command();
+        cmd.arg("--json")
+            .arg("--model=gpt-4o")
+            .arg("--compare-models=claude-sonnet")
+            .assert()
+            .success()
+            .stdout(contains("預估輸入成本 claude-sonnet"));
+
+        let output: serde_json::Value = serde_json::from_str(&env.read_output()).unwrap();
+        let costs = output["costs"].as_array().unwrap();
+        assert_eq!(costs.len(), 2);
+        assert_eq!(costs[0]["model"], "gpt-4o");
+        assert_eq!(costs[0]["input_price"], 2.5);
+        assert_eq!(costs[1]["model"], "claude-sonnet");
+    }
+
+    #[test]
+    fn test_split_into_numbered_files() {
+        let env = TestEnv::new();
+        let mut cmd = env.command();
+        cmd.arg("--split=200").assert().success();
+
+        let first = read_output_file(env.dir.path(), "output.001.txt");
+        let second = read_output_file(env.dir.path(), "output.002.txt");
+        assert!(contains("[Part 1 of").eval(&first));
+        assert!(contains("[Part 2 of").eval(&second));
+        assert!(contains("wait for the remaining parts").eval(&first));
+        assert!(!env.dir.path().join("output.txt").exists());
+
+        // Without an output file or the clipboard the parts go nowhere
+        let mut cmd = Command::cargo_bin("c2p").unwrap();
+        cmd.arg("path")
+            .arg(env.dir.path())
+            .arg("--lang=en")
+            .arg("--no-clipboard")
+            .arg("--split=200")
+            .assert()
+            .success()
+            .stdout(contains("未輸出到任何地方"));
+    }
+
+    #[test]
+    fn test_map_reduce_writes_group_prompts_and_index() {
+        let env = TestEnv::new();
+        let parts_dir = env.dir.path().join("parts");
+        let mut cmd = env.map_reduce_command();
+        cmd.arg("--map-reduce")
+            .arg(&parts_dir)
+            .arg("--model=gpt-4o")
+            .arg("--token-report=3")
+            .assert()
+            .success()
+            .stdout(contains("預估輸入成本 gpt-4o"))
+            .stdout(contains("Directory"));
+
+        let mut names: Vec<String> = fs::read_dir(&parts_dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+            .collect();
+        names.sort();
+        assert_eq!(names.len(), 3);
+        assert_eq!(names[0], "000-index.md");
+
+        let index = read_output_file(&parts_dir, "000-index.md");
+        assert!(contains("Manifest:").eval(&index));
+        assert!(contains("`lowercase`").eval(&index));
+        assert!(contains("`uppercase`").eval(&index));
+        assert!(contains("CONTENT FOO.PY").not().eval(&index));
+
+        let lowercase = names
+            .iter()
+            .find(|name| name.ends_with("-lowercase.md"))
+            .unwrap();
+        let part = read_output_file(&parts_dir, lowercase);
+        assert!(contains("content foo.py").eval(&part));
+        assert!(contains("CONTENT FOO.PY").not().eval(&part));
+    }
+
+    #[test]
+    fn test_glob_patterns_relative_to_root() {
+        init_logger();
+        let dir = tempdir().unwrap();
+        let root = dir.path().join("tests").join("project");
+        create_temp_file(&root, "src/main.rs", "fn main() {}");
+        create_temp_file(&root, "test_main.rs", "fn test_main() {}");
+        let output_file = dir.path().join("output.txt");
+
+        let mut cmd = Command::cargo_bin("c2p").unwrap();
+        cmd.arg("path")
+            .arg(&root)
+            .arg("--output")
+            .arg(&output_file)
+            .arg("--lang=en")
+            .arg("--no-clipboard")
+            .arg("--nor=test*")
+            .assert()
+            .success();
+
+        let output = read_output_file(dir.path(), "output.txt");
+        assert!(contains("fn main() {}").eval(&output));
+        assert!(contains("fn test_main() {}").not().eval(&output));
+    }
+
+    #[test]
+    fn test_legacy_patterns() {
+        let env = TestEnv::new();
+        let mut cmd = env.command();
+        cmd.arg("--legacy-patterns")
+            .arg("--nor=lowercase")
+            .assert()
+            .success();
+
+        let output = env.read_output();
+        assert!(contains("content foo.py").not().eval(&output));
+        assert!(contains("CONTENT FOO.PY").eval(&output));
+    }
+
+    #[test]
+    fn test_ordered_rules() {
+        let env = TestEnv::new();
+        let rule_file = env.dir.path().join("rules.txt");
+        fs::write(
+            &rule_file,
+            "# only python files\n*.txt\n!lowercase/qux.txt\n",
+        )
+        .unwrap();
+        let mut cmd = env.command();
+        cmd.arg("--rule-file")
+            .arg(&rule_file)
+            .arg("--rule")
+            .arg("-lowercase/")
+            .arg("--rule")
+            .arg("+lowercase/foo.py")
+            .assert()
+            .success();
+
+        let output = env.read_output();
+        assert!(contains("content foo.py").eval(&output));
+        assert!(contains("content bar.py").not().eval(&output));
+        assert!(contains("content qux.txt").not().eval(&output));
+        assert!(contains("CONTENT FOO.PY").eval(&output));
+        assert!(contains("CONTENT QUX.TXT").not().eval(&output));
+    }
+
+    #[test]
+    fn test_c2pignore_and_pattern_files() {
+        let env = TestEnv::new();
+        create_temp_file(env.dir.path(), "uppercase/.c2pignore", "*.txt\n!QUX.txt");
+        let nor_file = env.dir.path().join("exclude.txt");
+        fs::write(&nor_file, "# shared excludes\nlowercase/foo.py\n\nbar.py\n").unwrap();
+        let mut cmd = env.command();
+        cmd.arg("--nor-file").arg(&nor_file).assert().success();
+
+        let output = env.read_output();
+        assert!(contains("CONTENT CORGE.TXT").not().eval(&output));
+        assert!(contains("CONTENT QUX.TXT").eval(&output));
+        assert!(contains("content qux.txt").eval(&output));
+        assert!(contains("content foo.py").not().eval(&output));
+        assert!(contains("content bar.py").not().eval(&output));
+        assert!(contains("content baz.py").eval(&output));
+    }
+
+    #[test]
+    fn test_legacy_patterns_reject_negated_pattern_file_lines() {
+        let env = TestEnv::new();
+        let nor_file = env.dir.path().join("exclude.txt");
+        fs::write(&nor_file, "*.txt\n!QUX.txt\n").unwrap();
+        let mut cmd = env.command();
+        cmd.arg("--legacy-patterns")
+            .arg("--nor-file")
+            .arg(&nor_file)
+            .assert()
+            .failure()
+            .stderr(contains("否定模式 '!QUX.txt' 不支持 --legacy-patterns"));
+    }
+
+    #[test]
+    fn test_max_total_size_aborts() {
+        let env = TestEnv::new();
+        let mut cmd = env.command();
+        cmd.arg("--max-total-size=100")
+            .assert()
+            .failure()
+            .stderr(contains("超過上限 100 B"));
+    }
+
+    #[test]
+    fn test_explain_subcommand() {
+        let env = TestEnv::new();
+        let mut cmd = Command::cargo_bin("c2p").expect("Failed to find code2prompt binary");
+        cmd.arg("explain")
+            .arg(env.dir.path())
+            .arg("--in=*.py")
+            .arg("--nor=uppercase/")
+            .assert()
+            .success()
+            .stdout(contains("lowercase/foo.py (匹配包含模式 '*.py')"))
+            .stdout(contains("lowercase/qux.txt (未匹配任何包含模式)"))
+            .stdout(contains(
+                "uppercase/FOO.py (同時匹配包含模式 '*.py' 和排除模式 'uppercase/'，排除優先)",
+            ))
+            .stdout(contains("包含 3 個文件"));
+    }
 }
@@ -0,0 +1,34 @@
+use c2p::language::{detect_language, known_languages, parse_languages};
+use std::path::Path;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_detect_language() {
+        assert_eq!(detect_language(Path::new("src/main.rs")), Some("rust"));
+        assert_eq!(detect_language(Path::new("app/models.PY")), Some("python"));
+        assert_eq!(detect_language(Path::new("Dockerfile")), Some("dockerfile"));
+        assert_eq!(
+            detect_language(Path::new("docker/Dockerfile.dev")),
+            Some("dockerfile")
+        );
+        assert_eq!(detect_language(Path::new("Makefile")), Some("makefile"));
+        assert_eq!(detect_language(Path::new("BUILD")), Some("starlark"));
+        assert_eq!(detect_language(Path::new("BUILD.md")), Some("markdown"));
+        assert_eq!(detect_language(Path::new("Gemfile.lock")), None);
+        assert_eq!(detect_language(Path::new("CMakeLists.txt")), Some("cmake"));
+        assert_eq!(detect_language(Path::new("notes.txt")), Some("text"));
+        assert_eq!(detect_language(Path::new("data.bin")), None);
+        assert_eq!(detect_language(Path::new("LICENSE")), None);
+    }
+
+    #[test]
+    fn test_parse_languages() {
+        let languages = vec!["Rust".to_string(), " python ".to_string()];
+        assert_eq!(parse_languages(&languages).unwrap(), vec!["rust", "python"]);
+        assert!(parse_languages(&["klingon".to_string()]).is_err());
+        assert!(known_languages().contains(&"dockerfile"));
+    }
+}
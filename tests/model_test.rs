@@ -0,0 +1,111 @@
+use c2p::model::ModelRegistry;
+use c2p::token::get_model_info;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::path::PathBuf;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_builtin_registry() {
+        let registry = ModelRegistry::builtin();
+
+        let model = registry.get("gpt-4o").unwrap();
+        assert_eq!(model.encoding, "o200k");
+        assert_eq!(model.context_window, 128_000);
+
+        assert!(registry.get("claude-sonnet").unwrap().approximate);
+        assert!(registry.get("llama-3-70b").is_ok());
+        assert!(registry.get("unknown-model").is_err());
+    }
+
+    #[test]
+    fn test_extend_registry_from_toml() {
+        let dir = tempdir().unwrap();
+        let models_file = dir.path().join("models.toml");
+        fs::write(
+            &models_file,
+            r#"
+[[model]]
+name = "gpt-4o"
+encoding = "o200k"
+context_window = 64000
+
+[[model]]
+name = "qwen2.5-coder-7b"
+encoding = "cl100k"
+context_window = 32768
+approximate = true
+tokenizer_file = "/models/qwen/tokenizer.json"
+
+[[model]]
+name = "llama-local"
+encoding = "cl100k"
+context_window = 8192
+tokenizer_file = "tokenizers/llama.json"
+"#,
+        )
+        .unwrap();
+
+        let registry = ModelRegistry::load(Some(&models_file)).unwrap();
+        assert_eq!(registry.get("gpt-4o").unwrap().context_window, 64_000);
+
+        let added = registry.get("qwen2.5-coder-7b").unwrap();
+        assert_eq!(added.encoding, "cl100k");
+        assert_eq!(
+            added.tokenizer_file,
+            Some(PathBuf::from("/models/qwen/tokenizer.json"))
+        );
+        assert_eq!(
+            registry.models().len(),
+            ModelRegistry::builtin().models().len() + 2
+        );
+
+        // Relative tokenizer files are resolved against the directory of the models file
+        assert_eq!(
+            registry.get("llama-local").unwrap().tokenizer_file,
+            Some(dir.path().join("tokenizers/llama.json"))
+        );
+    }
+
+    #[test]
+    fn test_invalid_models_file() {
+        let mut registry = ModelRegistry::builtin();
+        assert!(registry
+            .extend_from_toml("[[model]]\nname = \"missing-fields\"")
+            .is_err());
+    }
+
+    #[test]
+    fn test_get_model_info_from_registry() {
+        let info = get_model_info(&None);
+        assert!(info.contains("gpt-4o"));
+        assert!(!info.contains("claude"));
+
+        assert!(get_model_info(&Some("gpt2".to_string())).contains("davinci"));
+        assert!(get_model_info(&Some("cl100k".to_string())).contains("gpt-4"));
+    }
+
+    #[test]
+    fn test_estimate_cost() {
+        let mut registry = ModelRegistry::builtin();
+
+        let estimate = registry.estimate_cost("gpt-4o", 2_000_000);
+        assert_eq!(estimate.input_price, Some(2.5));
+        assert_eq!(estimate.cost, Some(5.0));
+
+        let unpriced = registry.estimate_cost("llama-3-70b", 1_000);
+        assert_eq!(unpriced.cost, None);
+
+        registry
+            .extend_from_toml("[prices]\n\"gpt-4o\" = 1.0\n\"llama-3-70b\" = 0.5\n")
+            .unwrap();
+        assert_eq!(registry.estimate_cost("gpt-4o", 1_000_000).cost, Some(1.0));
+        assert_eq!(
+            registry.estimate_cost("llama-3-70b", 2_000_000).cost,
+            Some(1.0)
+        );
+    }
+}
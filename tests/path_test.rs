@@ -0,0 +1,567 @@
+use c2p::path::{format_size, parse_size, traverse_directory, TraverseOptions};
+use c2p::sort::{compare, SortFields, SortKey, SortOrder};
+use c2p::token::get_tokenizer;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+    use std::fs;
+    use std::path::PathBuf;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_traverse_directory_counts_tokens() {
+        let dir = tempdir().unwrap();
+        fs::create_dir_all(dir.path().join("src")).unwrap();
+        fs::write(dir.path().join("src/main.rs"), "fn main() {}\n").unwrap();
+        fs::write(
+            dir.path().join("README.md"),
+            "# Title\n\nSome words here.\n",
+        )
+        .unwrap();
+
+        let tokenizer = get_tokenizer(&None);
+        let (tree, files) =
+            traverse_directory(dir.path(), &TraverseOptions::default(), tokenizer.as_ref())
+                .unwrap();
+
+        assert!(tree.contains("main.rs"));
+        assert_eq!(files.len(), 2);
+        for file in &files {
+            let code = file["code"].as_str().unwrap();
+            let tokens = file["tokens"].as_u64().unwrap() as usize;
+            assert_eq!(tokens, tokenizer.count(code));
+        }
+    }
+
+    #[test]
+    fn test_traverse_directory_chunks_large_files() {
+        let dir = tempdir().unwrap();
+        let code: String = (0..40)
+            .map(|i| format!("fn function_{i}() {{\n    println!(\"{i}\");\n}}\n\n"))
+            .collect();
+        fs::write(dir.path().join("large.rs"), &code).unwrap();
+        fs::write(dir.path().join("small.rs"), "fn main() {}\n").unwrap();
+
+        let options = TraverseOptions {
+            max_file_tokens: Some(100),
+            ..TraverseOptions::default()
+        };
+        let tokenizer = get_tokenizer(&None);
+        let (_, files) = traverse_directory(dir.path(), &options, tokenizer.as_ref()).unwrap();
+
+        let chunks: Vec<_> = files
+            .iter()
+            .filter(|file| file["path"].as_str().unwrap().ends_with("large.rs"))
+            .collect();
+        assert!(chunks.len() > 1);
+        for chunk in &chunks {
+            assert_eq!(chunk["partial"], true);
+            let label = format!(
+                "Lines {}-{} of {}",
+                chunk["start_line"], chunk["end_line"], chunk["total_lines"]
+            );
+            assert!(chunk["code"].as_str().unwrap().starts_with(&label));
+        }
+
+        let small = files
+            .iter()
+            .find(|file| file["path"].as_str().unwrap().ends_with("small.rs"))
+            .unwrap();
+        assert!(small["partial"].is_null());
+    }
+
+    #[test]
+    fn test_traverse_directory_size_limits() {
+        let dir = tempdir().unwrap();
+        fs::write(
+            dir.path().join("dump.sql"),
+            "INSERT INTO t VALUES (1);\n".repeat(100),
+        )
+        .unwrap();
+        fs::write(dir.path().join("main.rs"), "fn main() {}\n").unwrap();
+        let tokenizer = get_tokenizer(&None);
+
+        let options = TraverseOptions {
+            max_file_size: Some(100),
+            ..TraverseOptions::default()
+        };
+        let (tree, files) = traverse_directory(dir.path(), &options, tokenizer.as_ref()).unwrap();
+        assert!(tree.contains("dump.sql"));
+        let dump = files
+            .iter()
+            .find(|file| file["path"].as_str().unwrap().ends_with("dump.sql"))
+            .unwrap();
+        assert_eq!(dump["skipped"], true);
+        assert_eq!(dump["size"], 2600);
+        assert!(!dump["code"].as_str().unwrap().contains("INSERT"));
+
+        let options = TraverseOptions {
+            max_total_size: Some(1000),
+            ..TraverseOptions::default()
+        };
+        assert!(traverse_directory(dir.path(), &options, tokenizer.as_ref()).is_err());
+
+        // Files left out after reading them do not count toward the total size
+        let grep_options = TraverseOptions {
+            grep: Some(regex::Regex::new("fn main").unwrap()),
+            ..options.clone()
+        };
+        let (_, files) = traverse_directory(dir.path(), &grep_options, tokenizer.as_ref()).unwrap();
+        assert_eq!(files.len(), 1);
+        fs::write(
+            dir.path().join("dump.sql"),
+            format!(
+                "-- @generated\n{}",
+                "INSERT INTO t VALUES (1);\n".repeat(100)
+            ),
+        )
+        .unwrap();
+        assert!(traverse_directory(dir.path(), &options, tokenizer.as_ref()).is_ok());
+    }
+
+    /// A tokenizer that fails the test when any text is tokenized.
+    struct UnusedTokenizer;
+
+    impl c2p::token::Tokenizer for UnusedTokenizer {
+        fn count(&self, _text: &str) -> usize {
+            panic!("no file should be tokenized");
+        }
+    }
+
+    #[test]
+    fn test_max_total_size_aborts_before_tokenizing() {
+        let dir = tempdir().unwrap();
+        fs::write(
+            dir.path().join("dump.sql"),
+            "INSERT INTO t VALUES (1);\n".repeat(100),
+        )
+        .unwrap();
+
+        let options = TraverseOptions {
+            max_total_size: Some(1000),
+            ..TraverseOptions::default()
+        };
+        assert!(traverse_directory(dir.path(), &options, &UnusedTokenizer).is_err());
+    }
+
+    #[test]
+    fn test_parse_and_format_size() {
+        assert_eq!(parse_size("1024").unwrap(), 1024);
+        assert_eq!(parse_size("10k").unwrap(), 10 * 1024);
+        assert_eq!(parse_size("1.5MB").unwrap(), 1024 * 1024 * 3 / 2);
+        assert_eq!(parse_size("2GiB").unwrap(), 2 << 30);
+        assert!(parse_size("10x").is_err());
+        assert!(parse_size("k").is_err());
+
+        assert_eq!(format_size(512), "512 B");
+        assert_eq!(format_size(300 * 1024 * 1024), "300.0 MB");
+    }
+
+    #[test]
+    fn test_traverse_directory_only_files() {
+        let dir = tempdir().unwrap();
+        fs::write(dir.path().join("changed.rs"), "fn changed() {}\n").unwrap();
+        fs::write(dir.path().join("unchanged.rs"), "fn unchanged() {}\n").unwrap();
+
+        let options = TraverseOptions {
+            only_files: Some(HashSet::from([PathBuf::from("changed.rs")])),
+            ..TraverseOptions::default()
+        };
+        let tokenizer = get_tokenizer(&None);
+        let (tree, files) = traverse_directory(dir.path(), &options, tokenizer.as_ref()).unwrap();
+
+        assert!(tree.contains("unchanged.rs"));
+        assert_eq!(files.len(), 1);
+        assert!(files[0]["path"].as_str().unwrap().ends_with("/changed.rs"));
+    }
+
+    #[test]
+    fn test_traverse_directory_language_filters() {
+        let dir = tempdir().unwrap();
+        fs::write(dir.path().join("main.rs"), "fn main() {}\n").unwrap();
+        fs::write(dir.path().join("tool.py"), "print('hi')\n").unwrap();
+        fs::write(dir.path().join("Dockerfile"), "FROM rust\n").unwrap();
+        let tokenizer = get_tokenizer(&None);
+
+        let options = TraverseOptions {
+            only_languages: vec!["rust".to_string(), "dockerfile".to_string()],
+            ..TraverseOptions::default()
+        };
+        let (_, files) = traverse_directory(dir.path(), &options, tokenizer.as_ref()).unwrap();
+        let mut languages: Vec<&str> = files
+            .iter()
+            .map(|file| file["language"].as_str().unwrap())
+            .collect();
+        languages.sort();
+        assert_eq!(languages, vec!["dockerfile", "rust"]);
+
+        let options = TraverseOptions {
+            skip_languages: vec!["rust".to_string()],
+            ..TraverseOptions::default()
+        };
+        let (_, files) = traverse_directory(dir.path(), &options, tokenizer.as_ref()).unwrap();
+        assert_eq!(files.len(), 2);
+        assert!(files.iter().all(|file| file["language"] != "rust"));
+    }
+
+    #[test]
+    fn test_traverse_directory_grep() {
+        let dir = tempdir().unwrap();
+        let code: String = (1..=20)
+            .map(|i| match i {
+                10 => "    return Err(\"connection reset\");\n".to_string(),
+                _ => format!("    let value_{i} = {i};\n"),
+            })
+            .collect();
+        fs::write(dir.path().join("client.rs"), &code).unwrap();
+        fs::write(dir.path().join("other.rs"), "fn other() {}\n").unwrap();
+        let tokenizer = get_tokenizer(&None);
+
+        let options = TraverseOptions {
+            grep: Some(regex::Regex::new("connection reset").unwrap()),
+            ..TraverseOptions::default()
+        };
+        let (_, files) = traverse_directory(dir.path(), &options, tokenizer.as_ref()).unwrap();
+        assert_eq!(files.len(), 1);
+        assert!(files[0]["code"].as_str().unwrap().contains("value_1 "));
+
+        let options = TraverseOptions {
+            grep_context: Some(1),
+            ..options
+        };
+        let (_, files) = traverse_directory(dir.path(), &options, tokenizer.as_ref()).unwrap();
+        assert_eq!(files.len(), 1);
+        let region = files[0]["code"].as_str().unwrap();
+        assert!(region.starts_with("Lines 9-11 of 20 (matching region):"));
+        assert!(!region.contains("value_1 "));
+
+        // Oversized regions are split by --max-file-tokens
+        let options = TraverseOptions {
+            grep_context: Some(5),
+            max_file_tokens: Some(20),
+            ..options
+        };
+        let (_, files) = traverse_directory(dir.path(), &options, tokenizer.as_ref()).unwrap();
+        assert!(files.len() > 1);
+        let first = files[0]["code"].as_str().unwrap();
+        assert!(first.starts_with("Lines 5-"));
+        let last = files.last().unwrap();
+        assert_eq!(last["end_line"], 15);
+        for file in &files {
+            assert_eq!(file["total_lines"], 20);
+        }
+    }
+
+    #[test]
+    fn test_traverse_directory_walker_toggles() {
+        let dir = tempdir().unwrap();
+        fs::create_dir_all(dir.path().join(".github/workflows")).unwrap();
+        fs::create_dir_all(dir.path().join("src/nested")).unwrap();
+        fs::write(dir.path().join(".github/workflows/ci.yml"), "on: push\n").unwrap();
+        fs::write(dir.path().join(".gitignore"), "build.log\n").unwrap();
+        fs::write(dir.path().join("build.log"), "output\n").unwrap();
+        fs::write(dir.path().join("main.rs"), "fn main() {}\n").unwrap();
+        fs::write(dir.path().join("src/nested/deep.rs"), "fn deep() {}\n").unwrap();
+        let tokenizer = get_tokenizer(&None);
+        let paths = |options: &TraverseOptions| -> HashSet<String> {
+            let (_, files) = traverse_directory(dir.path(), options, tokenizer.as_ref()).unwrap();
+            files
+                .iter()
+                .map(|file| {
+                    let path = file["path"].as_str().unwrap();
+                    path.split_once('/').unwrap().1.to_string()
+                })
+                .collect()
+        };
+
+        // Outside of a git repository .gitignore only applies on request
+        let default = paths(&TraverseOptions::default());
+        assert!(default.contains("build.log"));
+        assert!(!default.contains(".github/workflows/ci.yml"));
+
+        let options = TraverseOptions {
+            hidden: true,
+            ignore_without_git: true,
+            ..TraverseOptions::default()
+        };
+        let files = paths(&options);
+        assert!(files.contains(".github/workflows/ci.yml"));
+        assert!(files.contains(".gitignore"));
+        assert!(!files.contains("build.log"));
+
+        // The git directory stays out even with hidden files included
+        fs::create_dir_all(dir.path().join(".git")).unwrap();
+        fs::write(dir.path().join(".git/config"), "[core]\n").unwrap();
+        let files = paths(&options);
+        assert!(files.contains(".github/workflows/ci.yml"));
+        assert!(!files.contains(".git/config"));
+
+        let options = TraverseOptions {
+            no_ignore: true,
+            ..options
+        };
+        assert!(paths(&options).contains("build.log"));
+
+        let options = TraverseOptions {
+            max_depth: Some(1),
+            ..TraverseOptions::default()
+        };
+        let files = paths(&options);
+        assert!(files.contains("main.rs"));
+        assert!(!files.contains("src/nested/deep.rs"));
+    }
+
+    #[test]
+    fn test_traverse_directory_default_excludes() {
+        let dir = tempdir().unwrap();
+        fs::create_dir_all(dir.path().join("node_modules/left-pad")).unwrap();
+        fs::create_dir_all(dir.path().join("src")).unwrap();
+        fs::write(
+            dir.path().join("node_modules/left-pad/index.js"),
+            "module.exports = 1;\n",
+        )
+        .unwrap();
+        fs::write(dir.path().join("Cargo.lock"), "version = 3\n").unwrap();
+        fs::write(dir.path().join("src/app.min.js"), "var a=1;\n").unwrap();
+        fs::write(dir.path().join("src/app.js.map"), "{\"version\":3}\n").unwrap();
+        fs::write(dir.path().join("src/level.map"), "#..#\n").unwrap();
+        fs::write(
+            dir.path().join("src/api.rs"),
+            "// @generated by build.rs\nfn api() {}\n",
+        )
+        .unwrap();
+        fs::write(dir.path().join("src/main.rs"), "fn main() {}\n").unwrap();
+        let tokenizer = get_tokenizer(&None);
+
+        let (tree, files) =
+            traverse_directory(dir.path(), &TraverseOptions::default(), tokenizer.as_ref())
+                .unwrap();
+        assert_eq!(files.len(), 2);
+        assert!(files[0]["path"]
+            .as_str()
+            .unwrap()
+            .ends_with("src/level.map"));
+        assert!(files[1]["path"].as_str().unwrap().ends_with("src/main.rs"));
+        assert!(tree.contains("app.js.map [excluded by default]"));
+        assert!(tree.contains("node_modules [excluded by default]"));
+        assert!(tree.contains("Cargo.lock [excluded by default]"));
+        assert!(tree.contains("app.min.js [excluded by default]"));
+        assert!(tree.contains("api.rs [excluded by default]"));
+        assert!(!tree.contains("left-pad"));
+
+        let options = TraverseOptions {
+            no_default_excludes: true,
+            ..TraverseOptions::default()
+        };
+        let (tree, files) = traverse_directory(dir.path(), &options, tokenizer.as_ref()).unwrap();
+        assert_eq!(files.len(), 7);
+        assert!(!tree.contains("[excluded by default]"));
+    }
+
+    #[test]
+    fn test_explicit_includes_override_default_excludes() {
+        let dir = tempdir().unwrap();
+        fs::create_dir_all(dir.path().join("vendor/lib/src")).unwrap();
+        fs::create_dir_all(dir.path().join("dist")).unwrap();
+        fs::write(
+            dir.path().join("vendor/lib/src/lib.rs"),
+            "pub fn lib() {}\n",
+        )
+        .unwrap();
+        fs::write(dir.path().join("dist/app.js"), "var app = 1;\n").unwrap();
+        fs::write(dir.path().join("main.rs"), "fn main() {}\n").unwrap();
+        let tokenizer = get_tokenizer(&None);
+        let paths = |options: &TraverseOptions| -> Vec<String> {
+            let (_, files) = traverse_directory(dir.path(), options, tokenizer.as_ref()).unwrap();
+            files
+                .iter()
+                .map(|file| file["path"].as_str().unwrap().to_string())
+                .collect()
+        };
+
+        let options = TraverseOptions {
+            include: vec!["vendor/**".to_string()],
+            ..TraverseOptions::default()
+        };
+        let files = paths(&options);
+        assert_eq!(files.len(), 1);
+        assert!(files[0].ends_with("vendor/lib/src/lib.rs"));
+        let (tree, _) = traverse_directory(dir.path(), &options, tokenizer.as_ref()).unwrap();
+        assert!(!tree.contains("vendor [excluded by default]"));
+        assert!(tree.contains("dist [excluded by default]"));
+
+        // Paths no user rule matches are still left out by the profile
+        let options = TraverseOptions {
+            rules: vec!["-*.md".to_string(), "+dist/**".to_string()],
+            ..TraverseOptions::default()
+        };
+        let files = paths(&options);
+        assert_eq!(files.len(), 2);
+        assert!(files.iter().any(|path| path.ends_with("dist/app.js")));
+        assert!(!files.iter().any(|path| path.contains("vendor")));
+    }
+
+    #[test]
+    fn test_single_file_root_matches_include_patterns() {
+        let dir = tempdir().unwrap();
+        let file = dir.path().join("a.rs");
+        fs::write(&file, "fn a() {}\n").unwrap();
+        let tokenizer = get_tokenizer(&None);
+
+        let options = TraverseOptions {
+            include: vec!["*.rs".to_string()],
+            ..TraverseOptions::default()
+        };
+        let (_, files) = traverse_directory(&file, &options, tokenizer.as_ref()).unwrap();
+        assert_eq!(files.len(), 1);
+        assert!(files[0]["code"].as_str().unwrap().contains("fn a()"));
+
+        let options = TraverseOptions {
+            include: vec!["*.py".to_string()],
+            ..TraverseOptions::default()
+        };
+        let (_, files) = traverse_directory(&file, &options, tokenizer.as_ref()).unwrap();
+        assert!(files.is_empty());
+    }
+
+    #[test]
+    fn test_traverse_directory_extracts_symbols() {
+        let dir = tempdir().unwrap();
+        let code: String = (1..=30)
+            .map(|i| format!("fn function_{i}() {{\n    println!(\"{i}\");\n}}\n\n"))
+            .collect();
+        fs::write(dir.path().join("functions.rs"), &code).unwrap();
+        fs::write(dir.path().join("other.rs"), "fn other() {}\n").unwrap();
+
+        let options = TraverseOptions {
+            symbols: vec!["function_2".to_string(), "function_20".to_string()],
+            ..TraverseOptions::default()
+        };
+        let tokenizer = get_tokenizer(&None);
+        let (_, files) = traverse_directory(dir.path(), &options, tokenizer.as_ref()).unwrap();
+
+        assert_eq!(files.len(), 2);
+        let first = files[0]["code"].as_str().unwrap();
+        assert!(first.starts_with("Lines 5-7 of 120 (symbol definition):"));
+        assert!(first.contains("5 | fn function_2()"));
+        assert!(!first.contains("function_3"));
+        assert_eq!(files[1]["start_line"], 77);
+    }
+
+    #[test]
+    fn test_traverse_directory_output_is_deterministic() {
+        let dir = tempdir().unwrap();
+        for module in ["zeta", "alpha", "mid"] {
+            fs::create_dir_all(dir.path().join(module)).unwrap();
+            for i in (0..20).rev() {
+                fs::write(
+                    dir.path().join(module).join(format!("file_{i:02}.rs")),
+                    format!("fn f{i}() {{}}\n"),
+                )
+                .unwrap();
+            }
+        }
+        let tokenizer = get_tokenizer(&None);
+        let options = TraverseOptions::default();
+
+        let (tree, files) = traverse_directory(dir.path(), &options, tokenizer.as_ref()).unwrap();
+        let paths: Vec<&str> = files
+            .iter()
+            .map(|file| file["path"].as_str().unwrap())
+            .collect();
+        let mut sorted = paths.clone();
+        sorted.sort();
+        assert_eq!(paths.len(), 60);
+        assert_eq!(paths, sorted);
+        assert!(tree.find("alpha").unwrap() < tree.find("mid").unwrap());
+        assert!(tree.find("mid").unwrap() < tree.find("zeta").unwrap());
+
+        for _ in 0..3 {
+            let (other_tree, other_files) =
+                traverse_directory(dir.path(), &options, tokenizer.as_ref()).unwrap();
+            assert_eq!(other_tree, tree);
+            assert_eq!(other_files, files);
+        }
+    }
+
+    #[test]
+    fn test_traverse_directory_sorts_files_and_tree() {
+        let dir = tempdir().unwrap();
+        fs::create_dir_all(dir.path().join("lib")).unwrap();
+        fs::write(dir.path().join("lib/big.rs"), "x".repeat(300)).unwrap();
+        fs::write(dir.path().join("a.rs"), "x".repeat(200)).unwrap();
+        fs::write(dir.path().join("b.rs"), "x".repeat(100)).unwrap();
+        let tokenizer = get_tokenizer(&None);
+        let file_names = |files: &[serde_json::Value]| -> Vec<String> {
+            files
+                .iter()
+                .map(|file| {
+                    let path = file["path"].as_str().unwrap();
+                    path.split_once('/').unwrap().1.to_string()
+                })
+                .collect()
+        };
+
+        // Directories come first in the tree by default
+        let (tree, files) =
+            traverse_directory(dir.path(), &TraverseOptions::default(), tokenizer.as_ref())
+                .unwrap();
+        assert_eq!(file_names(&files), ["a.rs", "b.rs", "lib/big.rs"]);
+        assert!(tree.find("lib").unwrap() < tree.find("a.rs").unwrap());
+
+        let options = TraverseOptions {
+            sort: SortKey::Size,
+            ..TraverseOptions::default()
+        };
+        let (tree, files) = traverse_directory(dir.path(), &options, tokenizer.as_ref()).unwrap();
+        assert_eq!(file_names(&files), ["b.rs", "a.rs", "lib/big.rs"]);
+        assert!(tree.find("b.rs").unwrap() < tree.find("a.rs").unwrap());
+
+        let options = TraverseOptions {
+            sort: SortKey::Size,
+            sort_order: SortOrder::Desc,
+            no_dirs_first: true,
+            ..TraverseOptions::default()
+        };
+        let (tree, files) = traverse_directory(dir.path(), &options, tokenizer.as_ref()).unwrap();
+        assert_eq!(file_names(&files), ["lib/big.rs", "a.rs", "b.rs"]);
+        assert!(tree.find("lib").unwrap() < tree.find("a.rs").unwrap());
+        assert!(tree.find("a.rs").unwrap() < tree.find("b.rs").unwrap());
+
+        // Files of equal size are ordered by path
+        fs::write(dir.path().join("b.rs"), "x".repeat(200)).unwrap();
+        let (_, files) = traverse_directory(dir.path(), &options, tokenizer.as_ref()).unwrap();
+        assert_eq!(file_names(&files), ["lib/big.rs", "a.rs", "b.rs"]);
+
+        // A directory holding an uncommitted file is as recent as the file
+        let committed = SortFields {
+            path: PathBuf::from("old/a.rs"),
+            git_time: Some(100),
+            ..SortFields::default()
+        };
+        let uncommitted = SortFields {
+            path: PathBuf::from("new/b.rs"),
+            git_time: None,
+            ..SortFields::default()
+        };
+        let old = SortFields::aggregate(PathBuf::from("old"), [&committed]);
+        let new = SortFields::aggregate(PathBuf::from("new"), [&committed, &uncommitted]);
+        assert_eq!(old.git_time, Some(100));
+        assert_eq!(new.git_time, None);
+        assert_eq!(
+            compare(&old, &new, SortKey::GitRecency, SortOrder::Asc),
+            compare(
+                &committed,
+                &uncommitted,
+                SortKey::GitRecency,
+                SortOrder::Asc
+            )
+        );
+
+        assert!("git-recency".parse::<SortKey>().is_ok());
+        assert!("random".parse::<SortKey>().is_err());
+        assert!("up".parse::<SortOrder>().is_err());
+    }
+}
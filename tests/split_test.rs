@@ -0,0 +1,76 @@
+use c2p::split::{part_file_name, split_prompt};
+use c2p::token::get_tokenizer;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::{json, Value};
+
+    fn make_files(count: usize, lines: usize) -> (String, Vec<Value>) {
+        let mut rendered = String::from("Project Path: demo\n\n");
+        let mut files = Vec::new();
+        for index in 0..count {
+            let path = format!("demo/file_{}.rs", index);
+            let code: String = (0..lines)
+                .map(|line| format!("let value_{} = {};\n", line, line))
+                .collect();
+            rendered.push_str(&format!("`{}`:\n\n```rs\n{}```\n\n", path, code));
+            files.push(json!({ "path": path, "code": code }));
+        }
+        (rendered, files)
+    }
+
+    #[test]
+    fn test_split_prompt_on_file_boundaries() {
+        let tokenizer = get_tokenizer(&None);
+        let (rendered, files) = make_files(6, 20);
+
+        let parts = split_prompt(&rendered, &files, 500, tokenizer.as_ref()).unwrap();
+
+        assert!(parts.len() > 1);
+        for (index, part) in parts.iter().enumerate() {
+            assert!(tokenizer.count(part) <= 500);
+            assert!(part.starts_with(&format!("[Part {} of {}]", index + 1, parts.len())));
+        }
+        assert!(parts[0].contains("Do not respond yet"));
+        assert!(parts.last().unwrap().contains("All parts have been sent"));
+        // Every file starts at the beginning of a part or stays whole inside one.
+        for file in &files {
+            let header = format!("`{}`:", file["path"].as_str().unwrap());
+            assert_eq!(
+                parts.iter().filter(|part| part.contains(&header)).count(),
+                1
+            );
+        }
+    }
+
+    #[test]
+    fn test_split_prompt_oversized_file() {
+        let tokenizer = get_tokenizer(&None);
+        let (rendered, files) = make_files(1, 400);
+
+        let parts = split_prompt(&rendered, &files, 300, tokenizer.as_ref()).unwrap();
+
+        assert!(parts.len() > 2);
+        assert!(parts.iter().all(|part| tokenizer.count(part) <= 300));
+        assert!(parts.last().unwrap().contains("let value_399 = 399;"));
+    }
+
+    #[test]
+    fn test_split_prompt_limit_too_small() {
+        let tokenizer = get_tokenizer(&None);
+        let (rendered, files) = make_files(1, 5);
+
+        assert!(split_prompt(&rendered, &files, 10, tokenizer.as_ref()).is_err());
+    }
+
+    #[test]
+    fn test_part_file_name() {
+        assert_eq!(part_file_name("prompt.md", 1), "prompt.001.md");
+        assert_eq!(part_file_name("out/prompt", 12), "out/prompt.012");
+        assert_eq!(
+            part_file_name("out/my.prompt.txt", 3),
+            "out/my.prompt.003.txt"
+        );
+    }
+}
@@ -1,4 +1,7 @@
-use c2p::filter::should_include_file;
+use c2p::filter::{
+    build_glob_matcher, parse_pattern_file, parse_rule_file, should_include_file, FilterReason,
+    FilterSet, RuleAction, RuleSet,
+};
 use colored::*;
 use once_cell::sync::Lazy;
 use std::fs::{self, File};
@@ -9,10 +12,12 @@ use tempfile::{tempdir, TempDir};
 fn create_temp_file(dir: &Path, name: &str, content: &str) {
     let file_path = dir.join(name);
     let parent_dir = file_path.parent().unwrap();
-    fs::create_dir_all(parent_dir).expect(&format!("Failed to create directory: {:?}", parent_dir));
-    let mut file =
-        File::create(&file_path).expect(&format!("Failed to create temp file: {:?}", file_path));
-    writeln!(file, "{}", content).expect(&format!("Failed to write to temp file: {:?}", file_path));
+    fs::create_dir_all(parent_dir)
+        .unwrap_or_else(|_| panic!("Failed to create directory: {:?}", parent_dir));
+    let mut file = File::create(&file_path)
+        .unwrap_or_else(|_| panic!("Failed to create temp file: {:?}", file_path));
+    writeln!(file, "{}", content)
+        .unwrap_or_else(|_| panic!("Failed to write to temp file: {:?}", file_path));
 }
 
 static TEST_DIR: Lazy<TempDir> = Lazy::new(|| {
@@ -265,15 +270,13 @@ mod tests {
         let exclude_patterns = vec!["*/foo.py".to_string()];
         let include_priority = true;
 
-        for file in ["lowercase/foo.py"] {
-            let path = base_path.join(file);
-            assert!(should_include_file(
-                &path,
-                &include_patterns,
-                &exclude_patterns,
-                include_priority
-            ));
-        }
+        let path = base_path.join("lowercase/foo.py");
+        assert!(should_include_file(
+            &path,
+            &include_patterns,
+            &exclude_patterns,
+            include_priority
+        ));
 
         for file in [
             "lowercase/bar.py",
@@ -348,7 +351,7 @@ mod tests {
         let exclude_patterns: Vec<String> = vec![];
         let include_priority = false;
         assert!(should_include_file(
-            &path,
+            path,
             &include_patterns,
             &exclude_patterns,
             include_priority
@@ -362,10 +365,314 @@ mod tests {
         let exclude_patterns: Vec<String> = vec!["*.rs".to_string()];
         let include_priority = false;
         assert!(!should_include_file(
-            &path,
+            path,
             &include_patterns,
             &exclude_patterns,
             include_priority
         ));
     }
+
+    fn glob_included(include: &[&str], exclude: &[&str], path: &str, is_dir: bool) -> bool {
+        let root = Path::new("/home/me/tests/project");
+        let to_strings = |patterns: &[&str]| -> Vec<String> {
+            patterns.iter().map(|pattern| pattern.to_string()).collect()
+        };
+        RuleSet::from_patterns(root, &to_strings(include), &to_strings(exclude), false)
+            .unwrap()
+            .is_included(Path::new(path), is_dir)
+    }
+
+    #[test]
+    fn test_glob_patterns_are_relative_to_root() {
+        // The root lives under a `tests` directory, which must not match `test*`.
+        assert!(glob_included(&[], &["test*"], "src/main.rs", false));
+        assert!(!glob_included(&[], &["test*"], "tests/it.rs", false));
+        assert!(!glob_included(&[], &["test*"], "src/test_utils.rs", false));
+    }
+
+    #[test]
+    fn test_glob_pattern_syntax() {
+        assert!(glob_included(&["src/**/*.rs"], &[], "src/a/b/c.rs", false));
+        assert!(!glob_included(&["src/**/*.rs"], &[], "tests/c.rs", false));
+        assert!(glob_included(
+            &["src/{main,lib}.rs"],
+            &[],
+            "src/lib.rs",
+            false
+        ));
+        assert!(!glob_included(
+            &["src/{main,lib}.rs"],
+            &[],
+            "src/util.rs",
+            false
+        ));
+        assert!(glob_included(
+            &["file[abc].txt"],
+            &[],
+            "docs/fileb.txt",
+            false
+        ));
+        assert!(!glob_included(
+            &["file[abc].txt"],
+            &[],
+            "docs/filed.txt",
+            false
+        ));
+    }
+
+    #[test]
+    fn test_glob_directory_patterns() {
+        // A pattern matching a directory matches everything inside it.
+        assert!(!glob_included(&[], &["target/"], "target/debug/c2p", false));
+        assert!(glob_included(&[], &["target/"], "src/target.rs", false));
+        // A leading slash anchors the pattern at the root.
+        assert!(!glob_included(&[], &["/build"], "build/out.txt", false));
+        assert!(glob_included(&[], &["/build"], "src/build/out.txt", false));
+    }
+
+    #[test]
+    fn test_invalid_glob_pattern() {
+        let root = Path::new("/project");
+        assert!(build_glob_matcher(root, &["src/{main,lib.rs".to_string()]).is_err());
+    }
+
+    fn rules_included(rules: &[&str], path: &str) -> bool {
+        let mut rule_set = RuleSet::new(Path::new("/project"));
+        for rule in rules {
+            rule_set.push_rule(rule).unwrap();
+        }
+        rule_set.is_included(Path::new(path), false)
+    }
+
+    #[test]
+    fn test_rules_last_match_wins() {
+        let rules = ["+src/**", "-src/generated/**", "+src/generated/api.rs"];
+        assert!(rules_included(&rules, "src/main.rs"));
+        assert!(!rules_included(&rules, "src/generated/types.rs"));
+        assert!(rules_included(&rules, "src/generated/api.rs"));
+        // When the first rule includes, unmatched paths are excluded.
+        assert!(!rules_included(&rules, "README.md"));
+        // When the first rule excludes, unmatched paths are included.
+        let rules = ["-src/", "+src/lib.rs"];
+        assert!(rules_included(&rules, "README.md"));
+        assert!(rules_included(&rules, "src/lib.rs"));
+        assert!(!rules_included(&rules, "src/main.rs"));
+    }
+
+    #[test]
+    fn test_rules_from_patterns_follow_include_priority() {
+        let root = Path::new("/project");
+        let include = vec!["*.py".to_string()];
+        let exclude = vec!["foo.py".to_string()];
+
+        let rules = RuleSet::from_patterns(root, &include, &exclude, false).unwrap();
+        assert!(!rules.is_included(Path::new("src/foo.py"), false));
+        assert!(rules.is_included(Path::new("src/bar.py"), false));
+
+        let rules = RuleSet::from_patterns(root, &include, &exclude, true).unwrap();
+        assert!(rules.is_included(Path::new("src/foo.py"), false));
+        assert!(!rules.is_included(Path::new("src/notes.txt"), false));
+    }
+
+    #[test]
+    fn test_rules_ignore_whitelist_matches() {
+        // A negated glob matching a path is not a hit of its rule
+        let rules = ["-*.txt", "-!keep.txt"];
+        assert!(!rules_included(&rules, "notes.txt"));
+        assert!(!rules_included(&rules, "keep.txt"));
+        assert!(rules_included(&rules, "main.rs"));
+
+        let mut rule_set = RuleSet::new(Path::new("/project"));
+        for rule in rules {
+            rule_set.push_rule(rule).unwrap();
+        }
+        assert_eq!(
+            rule_set.explain(Path::new("keep.txt"), false),
+            (
+                false,
+                FilterReason::Pattern {
+                    action: RuleAction::Exclude,
+                    pattern: "*.txt".to_string()
+                }
+            )
+        );
+    }
+
+    #[test]
+    fn test_rules_matched_action_agrees_with_explain() {
+        let mut rule_set = RuleSet::new(Path::new("/project"));
+        for rule in ["+src/**", "-src/generated/**", "+src/generated/api.rs"] {
+            rule_set.push_rule(rule).unwrap();
+        }
+        assert_eq!(
+            rule_set.matched_action(Path::new("src/generated/types.rs"), false),
+            Some(RuleAction::Exclude)
+        );
+        assert_eq!(rule_set.matched_action(Path::new("README.md"), false), None);
+        for path in [
+            "src/main.rs",
+            "src/generated/types.rs",
+            "src/generated/api.rs",
+            "README.md",
+        ] {
+            let path = Path::new(path);
+            assert_eq!(
+                rule_set.is_included(path, false),
+                rule_set.explain(path, false).0
+            );
+        }
+    }
+
+    #[test]
+    fn test_invalid_rule() {
+        let mut rule_set = RuleSet::new(Path::new("/project"));
+        assert!(rule_set.push_rule("src/**").is_err());
+    }
+
+    #[test]
+    fn test_parse_rule_file() {
+        let content =
+            "# generated code\nsrc/generated/\n!src/generated/api.rs\n\n+docs/**\n-docs/drafts/\n";
+        assert_eq!(
+            parse_rule_file(content),
+            vec![
+                "-src/generated/",
+                "+src/generated/api.rs",
+                "+docs/**",
+                "-docs/drafts/"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_pattern_file() {
+        let content = "# build output\ntarget/\n\n  *.lock  \n";
+        assert_eq!(parse_pattern_file(content), vec!["target/", "*.lock"]);
+    }
+
+    #[test]
+    fn test_pattern_file_negation() {
+        let root = Path::new("/nonexistent/project");
+        let exclude = parse_pattern_file("*.txt\n!keep.txt\n");
+        assert_eq!(exclude, vec!["*.txt", "!keep.txt"]);
+
+        let filter_set = FilterSet::new(root, &[], &exclude, false, &[]).unwrap();
+        assert!(!filter_set.is_included(Path::new("notes.txt"), false));
+        assert_eq!(
+            filter_set.explain(Path::new("keep.txt"), false),
+            (true, FilterReason::Unfiltered)
+        );
+
+        // A negated include pattern excludes the paths it matches
+        let include = parse_pattern_file("src/\n!src/generated/\n");
+        let filter_set = FilterSet::new(root, &include, &[], false, &[]).unwrap();
+        assert!(filter_set.is_included(Path::new("src/main.rs"), false));
+        assert!(!filter_set.is_included(Path::new("src/generated/api.rs"), false));
+        assert!(!filter_set.is_included(Path::new("README.md"), false));
+    }
+
+    #[test]
+    fn test_negated_exclude_pattern_does_not_bypass_include_patterns() {
+        // A negated exclude line only cancels the exclusion and never adds a file
+        let root = Path::new("/nonexistent/project");
+        let include = vec!["*.rs".to_string()];
+        let exclude = parse_pattern_file("test_*\n!test_a.txt\n!test_b.rs\n");
+
+        let filter_set = FilterSet::new(root, &include, &exclude, false, &[]).unwrap();
+        assert_eq!(
+            filter_set.explain(Path::new("test_a.txt"), false),
+            (false, FilterReason::NoIncludeMatch)
+        );
+        assert!(filter_set.is_included(Path::new("test_b.rs"), false));
+        assert!(!filter_set.is_included(Path::new("test_c.rs"), false));
+        assert!(filter_set.is_included(Path::new("main.rs"), false));
+    }
+
+    #[test]
+    fn test_filter_set_matches_without_filesystem() {
+        // The root does not exist, so matching must not touch the filesystem.
+        let root = Path::new("/nonexistent/project");
+        let include = vec!["*.py".to_string()];
+        let exclude = vec!["lowercase/".to_string()];
+        let rules = vec!["+lowercase/foo.py".to_string()];
+
+        let filter_set = FilterSet::new(root, &include, &exclude, false, &rules).unwrap();
+        assert!(filter_set.is_included(Path::new("uppercase/FOO.py"), false));
+        assert!(filter_set.is_included(Path::new("lowercase/foo.py"), false));
+        assert!(!filter_set.is_included(Path::new("lowercase/bar.py"), false));
+        assert!(!filter_set.is_included(Path::new("uppercase/QUX.txt"), false));
+    }
+
+    #[test]
+    fn test_legacy_filter_set_matches_absolute_path() {
+        let root = Path::new("/nonexistent/project");
+        let include = vec!["*.py".to_string()];
+        let exclude = vec!["*project/lowercase*".to_string()];
+
+        let filter_set = FilterSet::legacy(root, &include, &exclude, false);
+        assert!(filter_set.is_included(Path::new("uppercase/FOO.py"), false));
+        assert!(!filter_set.is_included(Path::new("lowercase/foo.py"), false));
+        assert!(!filter_set.is_included(Path::new("uppercase/QUX.txt"), false));
+
+        let filter_set = FilterSet::legacy(root, &include, &exclude, true);
+        assert!(filter_set.is_included(Path::new("lowercase/foo.py"), false));
+    }
+
+    #[test]
+    fn test_legacy_invalid_include_pattern_matches_nothing() {
+        let root = Path::new("/nonexistent/project");
+        let include = vec!["src/(".to_string()];
+
+        let filter_set = FilterSet::legacy(root, &include, &[], false);
+        assert!(!filter_set.is_included(Path::new("src/main.rs"), false));
+        assert!(!filter_set.is_included(Path::new("src/(/main.rs"), false));
+        assert!(!should_include_file(
+            Path::new("/nonexistent/project/src/main.rs"),
+            &include,
+            &[],
+            false
+        ));
+    }
+
+    #[test]
+    fn test_filter_set_explains_deciding_pattern() {
+        let root = Path::new("/nonexistent/project");
+        let include = vec!["*.py".to_string()];
+        let exclude = vec!["lowercase/".to_string()];
+
+        let filter_set = FilterSet::new(root, &include, &exclude, false, &[]).unwrap();
+        assert_eq!(
+            filter_set.explain(Path::new("uppercase/FOO.py"), false),
+            (
+                true,
+                FilterReason::Pattern {
+                    action: RuleAction::Include,
+                    pattern: "*.py".to_string()
+                }
+            )
+        );
+        assert_eq!(
+            filter_set.explain(Path::new("lowercase/foo.py"), false),
+            (
+                false,
+                FilterReason::Conflict {
+                    include: "*.py".to_string(),
+                    exclude: "lowercase/".to_string(),
+                    winner: RuleAction::Exclude
+                }
+            )
+        );
+        assert_eq!(
+            filter_set.explain(Path::new("uppercase/QUX.txt"), false),
+            (false, FilterReason::NoIncludeMatch)
+        );
+
+        let filter_set = FilterSet::legacy(root, &include, &exclude, true);
+        assert!(filter_set.explain(Path::new("lowercase/foo.py"), false).0);
+        let filter_set = FilterSet::legacy(root, &[], &[], false);
+        assert_eq!(
+            filter_set.explain(Path::new("lowercase/foo.py"), false),
+            (true, FilterReason::Unfiltered)
+        );
+    }
 }
@@ -0,0 +1,66 @@
+use c2p::token::{get_tokenizer, load_tokenizer, HuggingFaceTokenizer, Tokenizer};
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::path::PathBuf;
+    use tempfile::tempdir;
+    use tiktoken_rs::{cl100k_base, o200k_base};
+
+    const WORD_LEVEL_TOKENIZER: &str = r#"{
+        "version": "1.0",
+        "truncation": { "direction": "Right", "max_length": 2, "strategy": "LongestFirst", "stride": 0 },
+        "padding": null,
+        "added_tokens": [],
+        "normalizer": null,
+        "pre_tokenizer": { "type": "Whitespace" },
+        "post_processor": null,
+        "decoder": null,
+        "model": {
+            "type": "WordLevel",
+            "vocab": { "hello": 0, "world": 1, "[UNK]": 2 },
+            "unk_token": "[UNK]"
+        }
+    }"#;
+
+    #[test]
+    fn test_get_tokenizer_tiktoken() {
+        let text = "fn main() { println!(\"Hello, world!\"); }";
+
+        let default = get_tokenizer(&None);
+        assert_eq!(
+            default.count(text),
+            o200k_base().unwrap().encode_with_special_tokens(text).len()
+        );
+
+        let cl100k = get_tokenizer(&Some("cl100k".to_string()));
+        assert_eq!(
+            cl100k.count(text),
+            cl100k_base()
+                .unwrap()
+                .encode_with_special_tokens(text)
+                .len()
+        );
+    }
+
+    #[test]
+    fn test_huggingface_tokenizer_from_file() {
+        let dir = tempdir().unwrap();
+        let tokenizer_file = dir.path().join("tokenizer.json");
+        fs::write(&tokenizer_file, WORD_LEVEL_TOKENIZER).unwrap();
+
+        let tokenizer = HuggingFaceTokenizer::from_file(&tokenizer_file).unwrap();
+        // Truncation from the file is disabled, so all five words are counted.
+        assert_eq!(tokenizer.count("hello world hello unknown words"), 5);
+
+        let loaded = load_tokenizer(&None, &Some(tokenizer_file)).unwrap();
+        assert_eq!(loaded.count("hello world"), 2);
+    }
+
+    #[test]
+    fn test_load_tokenizer_missing_file() {
+        let missing = Some(PathBuf::from("/nonexistent/tokenizer.json"));
+        assert!(load_tokenizer(&None, &missing).is_err());
+    }
+}
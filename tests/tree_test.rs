@@ -0,0 +1,119 @@
+use c2p::path::{traverse_directory, TraverseOptions};
+use c2p::token::get_tokenizer;
+use c2p::tree::{TreeAnnotation, TreeOptions};
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::tempdir;
+
+    fn render(root: &std::path::Path, tree: TreeOptions) -> String {
+        render_with(
+            root,
+            TraverseOptions {
+                tree,
+                ..TraverseOptions::default()
+            },
+        )
+    }
+
+    fn render_with(root: &std::path::Path, options: TraverseOptions) -> String {
+        let tokenizer = get_tokenizer(&None);
+        let (tree, _) = traverse_directory(root, &options, tokenizer.as_ref()).unwrap();
+        // Drop the name of the temporary root directory
+        tree.split_once('\n').unwrap().1.to_string()
+    }
+
+    #[test]
+    fn test_tree_depth_and_collapse() {
+        let dir = tempdir().unwrap();
+        fs::create_dir_all(dir.path().join("src/nested")).unwrap();
+        fs::write(dir.path().join("src/nested/deep.rs"), "fn deep() {}\n").unwrap();
+        fs::write(dir.path().join("src/lib.rs"), "mod nested;\n").unwrap();
+        fs::create_dir_all(dir.path().join("assets")).unwrap();
+        for i in 0..5 {
+            fs::write(dir.path().join(format!("assets/{i}.txt")), "asset\n").unwrap();
+        }
+        fs::create_dir_all(dir.path().join("docs")).unwrap();
+        fs::write(dir.path().join("docs/guide.md"), "# Guide\n").unwrap();
+        fs::write(dir.path().join("main.rs"), "fn main() {}\n").unwrap();
+
+        let tree = render(dir.path(), TreeOptions::default());
+        assert!(tree.contains("deep.rs"));
+        assert!(tree.contains("4.txt"));
+
+        let tree = render(
+            dir.path(),
+            TreeOptions {
+                max_depth: Some(1),
+                ..TreeOptions::default()
+            },
+        );
+        assert_eq!(
+            tree,
+            "├── assets\n│   └── (+5 files)\n├── docs\n│   └── (+1 file)\n├── src\n│   └── (+2 files)\n└── main.rs\n"
+        );
+
+        let tree = render(
+            dir.path(),
+            TreeOptions {
+                collapse: Some(4),
+                ..TreeOptions::default()
+            },
+        );
+        assert!(tree.contains("assets\n│   └── (+5 files)\n"));
+        assert!(tree.contains("deep.rs"));
+    }
+
+    #[test]
+    fn test_tree_annotations() {
+        let dir = tempdir().unwrap();
+        fs::create_dir_all(dir.path().join("src")).unwrap();
+        fs::write(dir.path().join("src/a.rs"), "fn a() {}\nfn b() {}\n").unwrap();
+        fs::write(dir.path().join("src/b.rs"), "fn c() {}\n").unwrap();
+        fs::write(dir.path().join("empty.txt"), "").unwrap();
+
+        let tree = render(
+            dir.path(),
+            TreeOptions {
+                annotations: vec![TreeAnnotation::Size, TreeAnnotation::Lines],
+                ..TreeOptions::default()
+            },
+        );
+        assert!(tree.contains("src (30 B, 3 lines)"));
+        assert!(tree.contains("a.rs (20 B, 2 lines)"));
+        // Files outside of the file list are only annotated with their size
+        assert!(tree.contains("empty.txt (0 B)\n"));
+
+        let tree = render(
+            dir.path(),
+            TreeOptions {
+                annotations: vec![TreeAnnotation::Tokens],
+                ..TreeOptions::default()
+            },
+        );
+        assert!(tree.contains("a.rs ("));
+        assert!(tree.contains(" tokens)"));
+
+        // Only the lines of the emitted definitions are counted
+        let tree = render_with(
+            dir.path(),
+            TraverseOptions {
+                symbols: vec!["b".to_string()],
+                tree: TreeOptions {
+                    annotations: vec![TreeAnnotation::Lines],
+                    ..TreeOptions::default()
+                },
+                ..TraverseOptions::default()
+            },
+        );
+        assert!(tree.contains("a.rs (1 line)"));
+
+        assert_eq!(
+            " Tokens".parse::<TreeAnnotation>().unwrap(),
+            TreeAnnotation::Tokens
+        );
+        assert!("words".parse::<TreeAnnotation>().is_err());
+    }
+}